@@ -0,0 +1,62 @@
+//! Compare parsing throughput against plain serde_json.
+//!
+//! ```text
+//! cargo run --release --example throughput [megabytes]
+//! ```
+
+use json_spanned_value::{self as jsv, spanned};
+
+use std::time::{Duration, Instant};
+
+fn main() {
+    let mb = std::env::args().nth(1).map_or(50, |a| a.parse::<usize>().expect("expected megabytes"));
+    let text = generate(mb * 1024 * 1024);
+    let bytes = text.as_bytes();
+    println!("input: {:.1} MB", text.len() as f64 / 1024.0 / 1024.0);
+    println!();
+
+    bench(&text, "serde_json::from_slice::<serde_json::Value>", || { serde_json::from_slice::<serde_json::Value>(bytes).unwrap(); });
+    bench(&text, "jsv::from_slice::<serde_json::Value>",        || { jsv::from_slice::<serde_json::Value>(bytes).unwrap(); });
+    bench(&text, "jsv::from_slice::<spanned::Value>",           || { jsv::from_slice::<spanned::Value>(bytes).unwrap(); });
+    bench(&text, "serde_json::from_reader::<serde_json::Value>", || { serde_json::from_reader::<_, serde_json::Value>(bytes).unwrap(); });
+    bench(&text, "jsv::from_reader::<spanned::Value>",          || { jsv::from_reader::<_, spanned::Value>(bytes).unwrap(); });
+    bench(&text, "serde_json::from_slice::<serde::de::IgnoredAny>", || { serde_json::from_slice::<serde::de::IgnoredAny>(bytes).unwrap(); });
+    bench(&text, "jsv::from_slice::<serde::de::IgnoredAny>",    || { jsv::from_slice::<serde::de::IgnoredAny>(bytes).unwrap(); });
+}
+
+fn bench(text: &str, name: &str, mut f: impl FnMut()) {
+    f(); // warmup
+    let mut best = Duration::from_secs(u64::MAX);
+    for _ in 0 .. 5 {
+        let start = Instant::now();
+        f();
+        best = best.min(start.elapsed());
+    }
+    let mbps = text.len() as f64 / 1024.0 / 1024.0 / best.as_secs_f64();
+    println!("{:<48} {:>8.1} ms {:>8.1} MB/s", name, best.as_secs_f64() * 1000.0, mbps);
+}
+
+/// Config-bundle-ish json: nested objects, string-heavy, with some numbers and escapes.
+fn generate(target: usize) -> String {
+    let mut text = String::from("[");
+    let mut i = 0u64;
+    while text.len() < target {
+        if i != 0 { text.push(','); }
+        text.push_str(&format!(concat!(
+            "\n  {{",
+            "\n    \"id\": {i},",
+            "\n    \"name\": \"server-{i}.example.com\",",
+            "\n    \"description\": \"A fairly long description of server number {i}, which exists purely to take up space.\",",
+            "\n    \"path\": \"C:\\\\Program Files\\\\server\\\\{i}\",",
+            "\n    \"enabled\": {enabled},",
+            "\n    \"weight\": {weight},",
+            "\n    \"offset\": -{i},",
+            "\n    \"tags\": [\"alpha\", \"beta\", \"gamma\", null],",
+            "\n    \"limits\": {{ \"cpu\": 0.5, \"memory\": 1073741824, \"timeout\": 30 }}",
+            "\n  }}",
+        ), i = i, enabled = i & 1 == 0, weight = i as f64 / 7.0));
+        i += 1;
+    }
+    text.push_str("\n]\n");
+    text
+}
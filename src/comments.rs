@@ -0,0 +1,142 @@
+use crate::{spanned, Value};
+
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::ops::Range;
+use std::vec;
+
+
+
+/// A `// line` or `/* block */` comment.
+///
+/// See [Deserializer::track_comments](crate::Deserializer::track_comments) and [from_str_with_comments](crate::from_str_with_comments).
+#[derive(Clone, Debug)]
+pub struct Comment {
+    range:  Range<usize>,
+    text:   String,
+    block:  bool,
+}
+
+impl Comment {
+    pub(crate) fn new(range: Range<usize>, text: String, block: bool) -> Self { Self { range, text, block } }
+
+    /// **Zero**-based byte range of the comment, including its `//` or `/*` `*/` delimiters, but not the end of the line.
+    pub fn range(&self) -> Range<usize> { self.range.clone() }
+
+    /// The text of the comment, without its delimiters (or the end of the line.)
+    pub fn text(&self) -> &str { &self.text }
+
+    /// `true` for `/* block */` comments, `false` for `// line` comments.
+    pub fn is_block(&self) -> bool { self.block }
+}
+
+
+
+/// Every [Comment] of a document, and what they're commenting on: the [spanned::Value] nodes and object members identified by [JSON pointers](https://tools.ietf.org/html/rfc6901).
+///
+/// *   [Leading](Self::leading) comments precede an object member or array element (or the root value.)
+/// *   [Trailing](Self::trailing) comments follow one on the same line, after any `,` (or anywhere after the root value.)
+///     This takes precedence - the comment in `[1, /* ? */ 2]` trails `/0`.
+/// *   [Dangling](Self::dangling) comments are inside an array or object, but neither of the above - such as the comments of an empty object.
+///
+/// ```
+/// # use json_spanned_value as jsv;
+/// let json = "{\n  // The port to listen on\n  \"port\": 8080, // not 80!\n  \"hosts\": [ /* none yet */ ]\n}";
+/// let settings = jsv::Settings { allow_comments: true, ..Default::default() };
+/// let (value, comments) = jsv::from_str_with_comments(json, &settings).unwrap();
+///
+/// assert_eq!(comments.leading("/port")[0].text(), " The port to listen on");
+/// assert_eq!(comments.trailing("/port")[0].text(), " not 80!");
+/// assert_eq!(comments.dangling("/hosts")[0].text(), " none yet ");
+/// assert_eq!(comments.all().len(), 3);
+/// # assert_eq!(value.pointer("/port").unwrap().as_number().unwrap().as_u64(), Some(8080));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Comments {
+    all:        Vec<Comment>,
+    leading:    HashMap<String, Vec<Comment>>,
+    trailing:   HashMap<String, Vec<Comment>>,
+    dangling:   HashMap<String, Vec<Comment>>,
+}
+
+impl Comments {
+    /// Attach `comments` parsed from `json` to the nodes of `value`, which was parsed from the same `json`.
+    pub fn new(json: &str, value: &spanned::Value, comments: Vec<Comment>) -> Self {
+        let mut sorted = comments.clone();
+        sorted.sort_by_key(|c| c.range.start);
+        let mut attach = Attach { json, comments: sorted.into_iter().peekable(), out: Comments { all: comments, ..Default::default() } };
+        attach.before(value.start(), None, Kind::Leading, "");
+        attach.node(value, &mut String::new());
+        attach.before(usize::MAX, None, Kind::Trailing, "");
+        attach.out
+    }
+
+    /// Every comment, in the order they were parsed.
+    pub fn all(&self) -> &[Comment] { &self.all }
+
+    /// Comments preceding the object member or array element at `pointer` (or the root value, if `""`.)
+    pub fn leading(&self, pointer: &str) -> &[Comment] { self.leading.get(pointer).map_or(&[], |c| &c[..]) }
+
+    /// Comments following the object member or array element at `pointer` on the same line (or following the root value at all, if `""`.)
+    pub fn trailing(&self, pointer: &str) -> &[Comment] { self.trailing.get(pointer).map_or(&[], |c| &c[..]) }
+
+    /// Comments inside the array or object at `pointer` not attached to any of its children.
+    pub fn dangling(&self, pointer: &str) -> &[Comment] { self.dangling.get(pointer).map_or(&[], |c| &c[..]) }
+}
+
+#[derive(Clone, Copy)]
+enum Kind { Leading, Trailing, Dangling }
+
+struct Attach<'a> {
+    json:       &'a str,
+    comments:   Peekable<vec::IntoIter<Comment>>,
+    out:        Comments,
+}
+
+impl Attach<'_> {
+    fn push(&mut self, kind: Kind, pointer: &str, comment: Comment) {
+        let map = match kind {
+            Kind::Leading   => &mut self.out.leading,
+            Kind::Trailing  => &mut self.out.trailing,
+            Kind::Dangling  => &mut self.out.dangling,
+        };
+        map.entry(pointer.to_string()).or_default().push(comment);
+    }
+
+    /// Attach comments starting before `until` to `pointer` - or as trailing comments of `prev`
+    /// (the end and pointer of the previous sibling), if on the same line.
+    fn before(&mut self, until: usize, prev: Option<(usize, &str)>, kind: Kind, pointer: &str) {
+        while self.comments.peek().map_or(false, |c| c.range.start < until) {
+            let comment = self.comments.next().unwrap();
+            match prev {
+                Some((end, prev)) if !self.json[end .. comment.range.start].contains('\n') => self.push(Kind::Trailing, prev, comment),
+                _other => self.push(kind, pointer, comment),
+            }
+        }
+    }
+
+    fn node(&mut self, node: &spanned::Value, pointer: &mut String) {
+        let mut children = match node.get_ref() {
+            Value::Array(a)     => a.iter().enumerate().map(|(i, v)| (v.start(), i.to_string(), v)).collect::<Vec<_>>(),
+            Value::Object(o)    => o.iter().map(|(k, v)| (k.start(), k.replace('~', "~0").replace('/', "~1"), v)).collect(),
+            _scalar             => return,
+        };
+        children.sort_by_key(|(start, _, _)| *start);
+
+        let parent = pointer.len();
+        let mut prev = None;
+        for (start, segment, value) in children {
+            pointer.truncate(parent);
+            pointer.push('/');
+            pointer.push_str(&segment);
+            let prev_pointer = prev.as_ref().map(|(end, p): &(usize, String)| (*end, &p[..]));
+            self.before(start, prev_pointer, Kind::Leading, pointer);
+            self.before(value.start(), None, Kind::Leading, pointer); // between an object key and its value
+            self.node(value, pointer);
+            prev = Some((value.end(), pointer.clone()));
+        }
+        pointer.truncate(parent);
+        let prev_pointer = prev.as_ref().map(|(end, p)| (*end, &p[..]));
+        self.before(node.end(), prev_pointer, Kind::Dangling, pointer);
+    }
+}
@@ -0,0 +1,1759 @@
+use crate::{map, spanned, Comment, DuplicateKey, DuplicateKeys, Error, IgnoredKey, Map, Result, Settings, Spanned, Token, TokenKind, Value};
+use crate::error::Category;
+use crate::reader::{IoRead, Read, Reference, SliceRead};
+
+use serde::de::{self, Visitor, DeserializeSeed, IntoDeserializer, Unexpected};
+use serde::de::value::BorrowedStrDeserializer;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::fmt::Display;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::io;
+use std::marker::PhantomData;
+use std::ops::Range;
+
+
+
+/// A span-tracking JSON parser implementing [serde::Deserializer].
+///
+/// All parse context - [Settings], and the positions [Spanned](crate::Spanned)
+/// values are built from - travels with the deserializer itself, so spans
+/// remain correct regardless of which thread ends up doing the deserializing.
+///
+/// Unlike feeding [serde_json] through an [io::Read], this knows exactly where
+/// every token starts and ends, and can borrow `&'de str`s straight out of the
+/// input buffer.
+///
+/// Use it directly to combine with serde adapters that wrap a deserializer,
+/// such as [serde_ignored](https://docs.rs/serde_ignored/) or
+/// [serde_path_to_error](https://docs.rs/serde_path_to_error/) - spans are
+/// still tracked through them.  Call [end](Self::end) afterwards to reject
+/// trailing characters.  Note that such adapters see each [Spanned](crate::Spanned)
+/// as a map wrapping its value, so paths they report may include an extra
+/// `$__json_spanned_value::private::value` segment - [Error::pointer] doesn't.
+///
+/// ```
+/// # use json_spanned_value::{self as jsv, spanned};
+/// let settings = jsv::Settings::default();
+/// let mut de = jsv::Deserializer::from_str("{\"a\": 1, \"b\": 2}", &settings);
+/// let mut ignored = Vec::new();
+/// let value : std::collections::BTreeMap<String, spanned::Value> = serde_ignored::deserialize(&mut de, |path| ignored.push(path.to_string())).unwrap();
+/// de.end().unwrap();
+/// assert_eq!(value["b"].range(), 14 .. 15);
+/// ```
+pub struct Deserializer<'de, R: Read<'de>> {
+    read:               R,
+    settings:           Settings,
+    scratch:            Vec<u8>,
+    remaining_depth:    u8,
+    /// Object keys most recently parsed at each depth, for [Error::pointer]
+    /// (and array indices too, while tracking ignored or duplicate keys.)
+    keys:               Vec<Vec<u8>>,
+    /// Byte offset just past the last token parsed
+    end:                usize,
+    /// `Some` while tracking [ignored keys](Self::track_ignored_keys)
+    ignored_keys:       Option<Vec<IgnoredKey>>,
+    /// Where the value of the object entry being deserialized starts, and the range of its key, while tracking ignored keys
+    entry:              Option<(usize, Range<usize>)>,
+    /// Keys parsed so far within the object at each depth, and their ranges, to enforce [Settings::duplicate_keys]
+    seen:               Vec<SeenKeys>,
+    /// Keys repeated so far, if [DuplicateKeys::Collect]ing them
+    duplicate_keys:     Vec<DuplicateKey>,
+    /// `Some` while tracking [comments](Self::track_comments)
+    comments:           Option<Vec<Comment>>,
+    /// Key offsets of earlier duplicates to skip within each struct's object, by the offset of its `{`,
+    /// while deserializing the outermost struct scanned for them under [DuplicateKeys::KeepLast] or [Collect](DuplicateKeys::Collect)
+    earlier:            Option<HashMap<usize, Vec<usize>>>,
+    /// `true` if the next object is a [Map] or [Value]'s, whose duplicate keys are errors even without [Settings::duplicate_keys]
+    unique:             bool,
+    /// `true` while skipping a value with [deserialize_ignored_any](de::Deserializer::deserialize_ignored_any), whose keys don't matter
+    ignoring:           bool,
+    _de:                PhantomData<&'de ()>,
+}
+
+impl<'de> Deserializer<'de, SliceRead<'de>> {
+    /// Deserialize json from an in-memory string, with explicit [Settings]
+    pub fn from_str(buf: &'de str, settings: &Settings) -> Self { Self::from_slice(buf.as_bytes(), settings) }
+
+    /// Deserialize json from a slice of in-memory bytes, with explicit [Settings]
+    pub fn from_slice(buf: &'de [u8], settings: &Settings) -> Self { Self::new(SliceRead::new(buf), settings) }
+}
+
+impl<'de, R: io::Read> Deserializer<'de, IoRead<R>> {
+    /// Deserialize json from an [io::Read], with explicit [Settings]
+    pub fn from_reader(reader: R, settings: &Settings) -> Self { Self::new(IoRead::new(reader), settings) }
+}
+
+/// Where the contents of a just-scanned string ended up.
+#[derive(Clone, Copy)]
+enum Scanned<'de> {
+    Borrowed(&'de [u8]),
+    Scratch,
+    Raw(usize, usize),
+}
+
+const RECURSION_LIMIT : u8 = 128;
+
+/// The keys of an object parsed so far, and their ranges.
+/// Most objects are small enough that a linear search beats hashing every key, and keys are never allocated individually.
+#[derive(Default)]
+struct SeenKeys {
+    /// Every key, concatenated
+    bytes:  Vec<u8>,
+    /// The end of each key within `bytes`, and its range within the input
+    keys:   Vec<(usize, Range<usize>)>,
+    /// Hashes of the keys, mapped to the latest index into `keys` with that hash, once there are too many to search linearly
+    index:  HashMap<u64, usize>,
+    /// The previous index into `keys` with the same hash as each indexed key
+    chain:  Vec<Option<usize>>,
+    hasher: RandomState,
+}
+
+const SEEN_KEYS_LINEAR_LIMIT : usize = 16;
+
+impl SeenKeys {
+    fn clear(&mut self) {
+        self.bytes.clear();
+        self.keys.clear();
+        self.index.clear();
+        self.chain.clear();
+    }
+
+    fn key(&self, i: usize) -> &[u8] {
+        let start = if i == 0 { 0 } else { self.keys[i - 1].0 };
+        &self.bytes[start .. self.keys[i].0]
+    }
+
+    fn hash(&self, key: &[u8]) -> u64 {
+        let mut hasher = self.hasher.build_hasher();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Add `keys[i]` to the index
+    fn link(&mut self, i: usize) {
+        let hash = self.hash(self.key(i));
+        let previous = self.index.insert(hash, i);
+        self.chain.push(previous);
+    }
+
+    /// Remember `key`, unless it was already seen - in which case, return the range of its first use.
+    fn insert(&mut self, key: &[u8], range: Range<usize>) -> Option<Range<usize>> {
+        let n = self.keys.len();
+        if n < SEEN_KEYS_LINEAR_LIMIT {
+            if let Some(i) = (0 .. n).find(|&i| self.key(i) == key) { return Some(self.keys[i].1.clone()) }
+        } else {
+            if self.chain.is_empty() { for i in 0 .. n { self.link(i); } }
+            let mut next = self.index.get(&self.hash(key)).copied();
+            while let Some(i) = next {
+                if self.key(i) == key { return Some(self.keys[i].1.clone()) }
+                next = self.chain[i];
+            }
+        }
+        self.bytes.extend_from_slice(key);
+        self.keys.push((self.bytes.len(), range));
+        if n >= SEEN_KEYS_LINEAR_LIMIT { self.link(n); }
+        None
+    }
+}
+
+enum Number {
+    U64(u64),
+    I64(i64),
+    F64(f64),
+}
+
+impl<'de, R: Read<'de>> Deserializer<'de, R> {
+    pub(crate) fn new(read: R, settings: &Settings) -> Self {
+        let mut settings = *settings;
+        if settings.allow_json5 {
+            settings.allow_comments         = true;
+            settings.allow_trailing_comma   = true;
+        }
+        #[allow(deprecated)] let allow_duplicate_keys = settings.allow_duplicate_keys;
+        if allow_duplicate_keys && settings.duplicate_keys.is_none() {
+            settings.duplicate_keys = Some(DuplicateKeys::KeepLast);
+        }
+        Self {
+            read, settings, scratch: Vec::new(), remaining_depth: RECURSION_LIMIT, keys: Vec::new(), end: 0,
+            ignored_keys: None, entry: None, seen: Vec::new(), duplicate_keys: Vec::new(), comments: None, earlier: None, unique: false, ignoring: false, _de: PhantomData,
+        }
+    }
+
+    /// **Zero**-based byte offset of the next unconsumed byte.
+    pub fn offset(&self) -> usize { self.read.offset() }
+
+    /// Start recording object keys whose values are skipped with [deserialize_ignored_any](de::Deserializer::deserialize_ignored_any),
+    /// such as unknown (or misspelled!) fields of structs without `#[serde(deny_unknown_fields)]`.
+    /// Collect them with [take_ignored_keys](Self::take_ignored_keys).
+    ///
+    /// ```
+    /// # use json_spanned_value as jsv;
+    /// # use serde::Deserialize;
+    /// #[derive(Deserialize)] struct Server { #[serde(default)] port: u16 }
+    ///
+    /// let json = "[{\"port\": 80}, {\"prot\": 8080}]";
+    /// let mut de = jsv::Deserializer::from_str(json, &jsv::Settings::default());
+    /// de.track_ignored_keys();
+    /// let servers = Vec::<Server>::deserialize(&mut de).unwrap();
+    /// de.end().unwrap();
+    ///
+    /// let ignored = de.take_ignored_keys();
+    /// assert_eq!(ignored.len(), 1);
+    /// assert_eq!(ignored[0].pointer(), "/1/prot");
+    /// assert_eq!(&json[ignored[0].key().range()], "\"prot\"");
+    /// ```
+    pub fn track_ignored_keys(&mut self) {
+        if self.ignored_keys.is_none() { self.ignored_keys = Some(Vec::new()); }
+    }
+
+    /// Take the keys ignored so far, if [tracking](Self::track_ignored_keys), in the order they were parsed.
+    pub fn take_ignored_keys(&mut self) -> Vec<IgnoredKey> {
+        self.ignored_keys.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Start recording `// line` and `/* block */` comments, if [Settings::allow_comments] (or [allow_json5](Settings::allow_json5).)
+    /// Collect them with [take_comments](Self::take_comments), or use [from_str_with_comments](crate::from_str_with_comments) to find out what they're commenting on.
+    pub fn track_comments(&mut self) {
+        if self.comments.is_none() { self.comments = Some(Vec::new()); }
+    }
+
+    /// Take the comments parsed so far, if [tracking](Self::track_comments), in order.
+    pub fn take_comments(&mut self) -> Vec<Comment> {
+        self.comments.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Take the repeated object keys found so far, in the order they were parsed.
+    /// Always empty unless [Settings::duplicate_keys] is `Some(`[DuplicateKeys::Collect]`)`.
+    ///
+    /// ```
+    /// # use json_spanned_value::{self as jsv, DuplicateKeys};
+    /// # use serde::Deserialize;
+    /// let json = "{\"a\": 1, \"b\": 2, \"a\": 3}";
+    /// let settings = jsv::Settings { duplicate_keys: Some(DuplicateKeys::Collect), ..Default::default() };
+    /// let mut de = jsv::Deserializer::from_str(json, &settings);
+    /// let map = std::collections::HashMap::<String, u32>::deserialize(&mut de).unwrap();
+    /// de.end().unwrap();
+    /// assert_eq!(map["a"], 3);
+    ///
+    /// let duplicates = de.take_duplicate_keys();
+    /// assert_eq!(duplicates.len(), 1);
+    /// assert_eq!(duplicates[0].pointer(), "/a");
+    /// assert_eq!(&json[duplicates[0].key().range()], "\"a\"");
+    /// assert_eq!(duplicates[0].first(), 1 .. 4);
+    /// ```
+    pub fn take_duplicate_keys(&mut self) -> Vec<DuplicateKey> {
+        std::mem::take(&mut self.duplicate_keys)
+    }
+
+    /// Deserialize a single `T` spanning the entire input.
+    pub(crate) fn deserialize_root<T: de::Deserialize<'de>>(&mut self) -> Result<T> {
+        self.deserialize_root_seed(PhantomData)
+    }
+
+    /// Deserialize a single `seed` spanning the entire input.
+    pub(crate) fn deserialize_root_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value> {
+        let result = seed.deserialize(&mut *self)
+            .map_err(|err| self.fix_position(err))
+            .and_then(|value| self.end().map(|()| value));
+        self.take_io_error(result)
+    }
+
+    fn take_io_error<T>(&mut self, result: Result<T>) -> Result<T> {
+        match self.read.take_error() {
+            Some(err)   => Err(Error::io(err)),
+            None        => result,
+        }
+    }
+
+    /// Check that only whitespace (and comments, if allowed) remain.
+    /// Should be called after deserializing a value, to reject input like `{} trailing`.
+    pub fn end(&mut self) -> Result<()> {
+        let result = match self.parse_whitespace() {
+            Ok(Some(_)) => Err(self.peek_error("trailing characters")),
+            Ok(None)    => Ok(()),
+            Err(err)    => Err(err),
+        };
+        self.take_io_error(result)
+    }
+
+    /// Check that a value which doesn't delimit itself (e.g. `123`) is followed by a token boundary.
+    fn peek_end_of_value(&mut self) -> Result<()> {
+        match self.read.peek() {
+            Some(b' ') | Some(b'\n') | Some(b'\t') | Some(b'\r') | Some(b'"') | Some(b'[') | Some(b']') | Some(b'{') | Some(b'}') | Some(b',') | Some(b':') | None => Ok(()),
+            Some(b'/') if self.settings.allow_comments => Ok(()),
+            Some(b'\'') if self.settings.allow_json5 => Ok(()),
+            Some(_) if self.json5_whitespace_len() != 0 => Ok(()),
+            Some(_) => Err(self.peek_error("trailing characters")),
+        }
+    }
+
+    /// Deserialize the next whitespace-separated value for a [StreamDeserializer](crate::StreamDeserializer), or `None` at EOF.
+    pub(crate) fn deserialize_next<T: de::Deserialize<'de>>(&mut self) -> Result<Option<T>> {
+        let peek = match self.parse_whitespace()? {
+            None => return Ok(None),
+            Some(peek) => peek,
+        };
+        let value = T::deserialize(&mut *self).map_err(|err| self.fix_position(err))?;
+        // https://github.com/serde-rs/json/blob/v1.0.58/src/de.rs#L2166-L2180
+        let self_delineated = matches!(peek, b'[' | b'{' | b'"' | b'\'');
+        if !self_delineated { self.peek_end_of_value()?; }
+        Ok(Some(value))
+    }
+
+    /// Record the end of the last value for [Spanned](crate::Spanned).
+    fn end_value(&mut self) {
+        self.end = self.read.offset();
+    }
+
+    /// A syntax error detected at `offset`, spanning from there up to the current position (or the character at `offset` if there.)
+    fn error_at(&self, offset: usize, msg: impl Display) -> Error {
+        let msg = msg.to_string();
+        let category = if msg.starts_with("EOF") { Category::Eof } else { Category::Syntax };
+        let current = self.read.offset();
+        let end = if offset < current { current } else {
+            offset + match self.read.buffered().first() {
+                None                => 0,
+                Some(0xF0 ..= 0xFF) => 4,
+                Some(0xE0 ..= 0xEF) => 3,
+                Some(0xC0 ..= 0xDF) => 2,
+                Some(_)             => 1,
+            }
+        };
+        let mut err = Error::new(category, msg);
+        err.set_position(offset, self.read.position_of(offset), offset .. end);
+        err
+    }
+
+    fn peek_error(&self, msg: impl Display) -> Error {
+        self.error_at(self.read.offset(), msg)
+    }
+
+    /// Attach the current position to errors created without one, such as [de::Error::custom] from a [Visitor].
+    fn fix_position(&self, err: Error) -> Error {
+        self.fix_range(self.read.offset(), err)
+    }
+
+    /// Attach the current position, and a range starting at `start`, to errors created without one.
+    fn fix_range(&self, start: usize, mut err: Error) -> Error {
+        if !err.has_position() {
+            match err.range() {
+                // e.g. from crate::error_at
+                Some(range) => err.set_position(range.start, self.read.position_of(range.start), range),
+                None => {
+                    let offset = self.read.offset();
+                    err.set_position(offset, self.read.position_of(offset), start.min(offset) .. offset);
+                },
+            }
+        }
+        err
+    }
+
+    /// Skip whitespace and comments (if allowed), returning the next byte without consuming it.
+    #[inline]
+    fn parse_whitespace(&mut self) -> Result<Option<u8>> {
+        // Usually there's nothing to skip - keep that cheap enough to inline
+        match self.read.peek() {
+            Some(b' ') | Some(b'\n') | Some(b'\t') | Some(b'\r') | Some(b'/') => self.skip_whitespace(),
+            Some(0x0B) | Some(0x0C) | Some(0xC2 ..= 0xEF) if self.settings.allow_json5 => self.skip_whitespace(),
+            other => Ok(other),
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> Result<Option<u8>> {
+        loop {
+            match self.read.peek() {
+                Some(b' ') | Some(b'\n') | Some(b'\t') | Some(b'\r') => {
+                    let buffered = self.read.peek_buffered();
+                    let n = buffered.iter().position(|b| !matches!(b, b' ' | b'\n' | b'\t' | b'\r')).unwrap_or(buffered.len());
+                    self.read.discard(n);
+                },
+                Some(b'/') if self.settings.allow_comments => match self.read.peek_at(1) {
+                    Some(b'/') => {
+                        let start = self.read.offset();
+                        self.read.discard(2);
+                        let mut text = self.comments.as_ref().map(|_| Vec::new());
+                        loop {
+                            let buffered = self.read.peek_buffered();
+                            if buffered.is_empty() { break }
+                            let (n, newline) = match buffered.iter().position(|b| *b == b'\n') {
+                                Some(n) => (n, true),
+                                None    => (buffered.len(), false),
+                            };
+                            if let Some(text) = text.as_mut() { text.extend_from_slice(&buffered[..n]); }
+                            self.read.discard(n);
+                            if newline { break }
+                        }
+                        let mut end = self.read.offset();
+                        if self.read.peek() == Some(b'\n') { self.read.discard(1); }
+                        if let Some(mut text) = text {
+                            if text.last() == Some(&b'\r') { text.pop(); end -= 1; }
+                            self.push_comment(start .. end, text, false);
+                        }
+                    },
+                    Some(b'*') => {
+                        let start = self.read.offset();
+                        self.read.discard(2);
+                        let mut text = self.comments.as_ref().map(|_| Vec::new());
+                        loop {
+                            match self.read.peek() {
+                                None        => return Err(self.error_at(start, "EOF while parsing a comment")),
+                                Some(b'*') if self.read.peek_at(1) == Some(b'/') => { self.read.discard(2); break }
+                                Some(ch)    => { if let Some(text) = text.as_mut() { text.push(ch); } self.read.discard(1) },
+                            }
+                        }
+                        if let Some(text) = text { self.push_comment(start .. self.read.offset(), text, true); }
+                    },
+                    _other => return Ok(Some(b'/')), // Okay, not actually a comment I guess
+                },
+                other => match self.json5_whitespace_len() {
+                    0 => return Ok(other),
+                    n => self.read.discard(n),
+                },
+            }
+        }
+    }
+
+    fn push_comment(&mut self, range: Range<usize>, text: Vec<u8>, block: bool) {
+        let text = String::from_utf8(text).unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned());
+        if let Some(comments) = self.comments.as_mut() { comments.push(Comment::new(range, text, block)); }
+    }
+
+    /// Length of the [JSON5 whitespace](https://spec.json5.org/#white-space) beyond JSON's own that's next, or `0` if none (or not parsing JSON5.)
+    fn json5_whitespace_len(&mut self) -> usize {
+        if !self.settings.allow_json5 { return 0 }
+        match (self.read.peek(), self.read.peek_at(1), self.read.peek_at(2)) {
+            (Some(0x0B), _, _) | (Some(0x0C), _, _)                         => 1, // \v, \f
+            (Some(0xC2), Some(0xA0), _)                                     => 2, // U+00A0 no-break space
+            (Some(0xE1), Some(0x9A), Some(0x80))                            => 3, // U+1680 ogham space mark
+            (Some(0xE2), Some(0x80), Some(0x80 ..= 0x8A))                   => 3, // U+2000 ..= U+200A
+            (Some(0xE2), Some(0x80), Some(0xA8)) | (Some(0xE2), Some(0x80), Some(0xA9)) => 3, // U+2028 line separator, U+2029 paragraph separator
+            (Some(0xE2), Some(0x80), Some(0xAF))                            => 3, // U+202F narrow no-break space
+            (Some(0xE2), Some(0x81), Some(0x9F))                            => 3, // U+205F medium mathematical space
+            (Some(0xE3), Some(0x80), Some(0x80))                            => 3, // U+3000 ideographic space
+            (Some(0xEF), Some(0xBB), Some(0xBF))                            => 3, // U+FEFF byte order mark
+            _other                                                          => 0,
+        }
+    }
+
+    fn parse_ident(&mut self, ident: &[u8]) -> Result<()> {
+        for expected in ident {
+            match self.read.peek() {
+                None                        => return Err(self.peek_error("EOF while parsing a value")),
+                Some(ch) if ch == *expected => self.read.discard(1),
+                Some(_)                     => return Err(self.peek_error("expected ident")),
+            }
+        }
+        self.end_value();
+        Ok(())
+    }
+
+    fn parse_number(&mut self) -> Result<Number> {
+        let start = self.read.offset();
+        self.scratch.clear();
+        let mut integral = true;
+
+        let json5 = self.settings.allow_json5;
+        let negative = self.read.peek() == Some(b'-');
+        if negative { self.scratch.push(b'-'); self.read.discard(1); }
+        else if json5 && self.read.peek() == Some(b'+') { self.read.discard(1); }
+
+        if json5 {
+            let sign = if negative { -1.0 } else { 1.0 };
+            match (self.read.peek(), self.read.peek_at(1)) {
+                (Some(b'I'), _)                                 => { self.parse_ident(b"Infinity")?; return Ok(Number::F64(sign * f64::INFINITY)) },
+                (Some(b'N'), _)                                 => { self.parse_ident(b"NaN")?; return Ok(Number::F64(sign * f64::NAN)) },
+                (Some(b'0'), Some(b'x')) | (Some(b'0'), Some(b'X')) => return self.parse_hex_number(start, negative),
+                _other                                          => {},
+            }
+        }
+
+        // Accumulate the integer part directly, so the common case never has to re-parse `scratch`
+        let mut significand = Some(0u64);
+        match self.read.peek() {
+            Some(b'0') => {
+                self.scratch.push(b'0');
+                self.read.discard(1);
+                if let Some(b'0' ..= b'9') = self.read.peek() { return Err(self.peek_error("invalid number")) }
+            },
+            Some(b'1' ..= b'9') => loop {
+                let buffered = self.read.peek_buffered();
+                let n = buffered.iter().position(|b| !b.is_ascii_digit()).unwrap_or(buffered.len());
+                for d in &buffered[..n] {
+                    significand = significand.and_then(|s| s.checked_mul(10)).and_then(|s| s.checked_add(u64::from(d - b'0')));
+                }
+                self.scratch.extend_from_slice(&buffered[..n]);
+                let more = n == buffered.len() && n != 0;
+                self.read.discard(n);
+                if !more { break }
+            },
+            Some(b'.') if json5 && matches!(self.read.peek_at(1), Some(b'0' ..= b'9')) => self.scratch.push(b'0'), // leading decimal point
+            _other => return Err(self.peek_error("invalid number")),
+        }
+
+        if self.read.peek() == Some(b'.') {
+            integral = false;
+            self.scratch.push(b'.');
+            self.read.discard(1);
+            match self.read.peek() {
+                Some(b'0' ..= b'9') => self.take_digits(),
+                _other if json5     => self.scratch.push(b'0'), // trailing decimal point
+                _other              => return Err(self.peek_error("invalid number")),
+            }
+        }
+
+        if let Some(e @ b'e') | Some(e @ b'E') = self.read.peek() {
+            integral = false;
+            self.scratch.push(e);
+            self.read.discard(1);
+            if let Some(sign @ b'+') | Some(sign @ b'-') = self.read.peek() { self.scratch.push(sign); self.read.discard(1); }
+            if !matches!(self.read.peek(), Some(b'0' ..= b'9')) { return Err(self.peek_error("invalid number")) }
+            self.take_digits();
+        }
+
+        self.end_value();
+        match (integral, negative, significand) {
+            (true, false, Some(n))                              => return Ok(Number::U64(n)),
+            (true, true,  Some(0))                              => return Ok(Number::F64(-0.0)),
+            (true, true,  Some(n)) if n <= 1 << 63              => return Ok(Number::I64(0i64.wrapping_sub(n as i64))),
+            _other                                              => {},
+        }
+        let text = std::str::from_utf8(&self.scratch).expect("number should only contain ASCII");
+        match text.parse::<f64>() {
+            Ok(f) if f.is_finite()  => Ok(Number::F64(f)),
+            _other                  => Err(self.error_at(start, "number out of range")),
+        }
+    }
+
+    /// JSON5 hexadecimal integer such as `0xC0FFEE`, after any sign.
+    fn parse_hex_number(&mut self, start: usize, negative: bool) -> Result<Number> {
+        self.read.discard(2); // b"0x"
+        let mut n = 0u64;
+        let mut digits = 0;
+        loop {
+            let digit = match self.read.peek() {
+                Some(ch @ b'0'..=b'9')  => ch - b'0',
+                Some(ch @ b'a'..=b'f')  => ch - b'a' + 10,
+                Some(ch @ b'A'..=b'F')  => ch - b'A' + 10,
+                _other                  => break,
+            };
+            n = match n.checked_mul(16) {
+                Some(n) => n + u64::from(digit),
+                None    => return Err(self.error_at(start, "number out of range")),
+            };
+            digits += 1;
+            self.read.discard(1);
+        }
+        if digits == 0 { return Err(self.peek_error("invalid number")) }
+        self.end_value();
+        match (negative, n) {
+            (false, n)                  => Ok(Number::U64(n)),
+            (true,  0)                  => Ok(Number::F64(-0.0)),
+            (true,  n) if n <= 1 << 63  => Ok(Number::I64(0i64.wrapping_sub(n as i64))),
+            (true,  _)                  => Err(self.error_at(start, "number out of range")),
+        }
+    }
+
+    fn take_digits(&mut self) {
+        loop {
+            let buffered = self.read.peek_buffered();
+            let n = buffered.iter().position(|b| !b.is_ascii_digit()).unwrap_or(buffered.len());
+            self.scratch.extend_from_slice(&buffered[..n]);
+            let more = n == buffered.len() && n != 0;
+            self.read.discard(n);
+            if !more { break }
+        }
+    }
+
+    /// Scan a string whose opening `"` (or JSON5 `'`) is next, consuming through the closing quote.
+    fn scan_str(&mut self) -> Result<Scanned<'de>> {
+        let quote = self.read.peek().unwrap_or(b'"');
+        self.read.discard(1);
+        let start = self.read.offset();
+        self.scratch.clear();
+        let mut copying = self.read.raw_since(start).is_none();
+
+        loop {
+            match self.read.peek() {
+                None => return Err(self.peek_error("EOF while parsing a string")),
+                Some(ch) if ch == quote => break,
+                Some(b'\\') => {
+                    if !copying {
+                        copying = true;
+                        let raw = self.read.raw_since(start).unwrap_or(&[]);
+                        self.scratch.extend_from_slice(raw);
+                    }
+                    self.read.discard(1);
+                    self.parse_escape()?;
+                },
+                Some(ch) if ch < 0x20 && (!self.settings.allow_json5 || ch == b'\n' || ch == b'\r') => return Err(self.peek_error("control character (\\u0000-\\u001F) found while parsing a string")),
+                Some(ch) if ch < 0x20 => {
+                    if copying { self.scratch.push(ch); }
+                    self.read.discard(1);
+                },
+                Some(_) => {
+                    // Skip ahead to the next byte needing special attention in one go
+                    let buffered = self.read.peek_buffered();
+                    let n = buffered.iter().position(|&b| b == quote || b == b'\\' || b < 0x20).unwrap_or(buffered.len());
+                    if copying { self.scratch.extend_from_slice(&buffered[..n]); }
+                    self.read.discard(n);
+                },
+            }
+        }
+
+        let len = self.read.offset() - start;
+        let scanned = if copying {
+            Scanned::Scratch
+        } else if let Some(b) = self.read.borrow_since(start) {
+            Scanned::Borrowed(b)
+        } else {
+            Scanned::Raw(start, len)
+        };
+        self.read.discard(1); // quote
+        self.end_value();
+        Ok(scanned)
+    }
+
+    /// Scan a JSON5 unquoted object key such as `foo` or `$bar_2`.
+    fn scan_identifier(&mut self) -> Result<Scanned<'de>> {
+        let start = self.read.offset();
+        self.scratch.clear();
+        let copying = self.read.raw_since(start).is_none();
+        loop {
+            match self.read.peek() {
+                // Non-ASCII is assumed to be a Unicode letter - ID_Start / ID_Continue aren't checked in detail
+                Some(ch) if ch >= 0x80 && self.json5_whitespace_len() != 0 => break,
+                Some(ch) if ch == b'$' || ch == b'_' || ch.is_ascii_alphabetic() || (ch.is_ascii_digit() && self.read.offset() != start) || ch >= 0x80 => {
+                    if copying { self.scratch.push(ch); }
+                    self.read.discard(1);
+                },
+                Some(b'\\') => return Err(self.peek_error("escapes in unquoted keys are not supported")),
+                _other => break,
+            }
+        }
+        if self.read.offset() == start { return Err(self.peek_error("key must be a string")) }
+
+        let len = self.read.offset() - start;
+        let scanned = if copying {
+            Scanned::Scratch
+        } else if let Some(b) = self.read.borrow_since(start) {
+            Scanned::Borrowed(b)
+        } else {
+            Scanned::Raw(start, len)
+        };
+        self.end_value();
+        Ok(scanned)
+    }
+
+    fn scanned_bytes(&self, scanned: Scanned<'de>) -> Reference<'de, '_, [u8]> {
+        match scanned {
+            Scanned::Borrowed(b)        => Reference::Borrowed(b),
+            Scanned::Scratch            => Reference::Copied(&self.scratch),
+            Scanned::Raw(start, len)    => Reference::Copied(&self.read.raw_since(start).unwrap_or(&[])[..len]),
+        }
+    }
+
+    fn parse_str_bytes(&mut self) -> Result<Reference<'de, '_, [u8]>> {
+        let scanned = self.scan_str()?;
+        Ok(self.scanned_bytes(scanned))
+    }
+
+    fn parse_str(&mut self) -> Result<Reference<'de, '_, str>> {
+        let start = self.read.offset();
+        let scanned = self.scan_str()?;
+        self.scanned_str(start, scanned)
+    }
+
+    /// Parse an object key: a string, or a JSON5 identifier.
+    fn parse_key(&mut self) -> Result<Reference<'de, '_, str>> {
+        let start = self.read.offset();
+        let scanned = match self.read.peek() {
+            Some(b'"') | Some(b'\'') => self.scan_str()?,
+            _other => self.scan_identifier()?,
+        };
+
+        let depth = self.depth();
+        if self.keys.len() <= depth { self.keys.resize_with(depth + 1, Vec::new); }
+        let key = &mut self.keys[depth];
+        key.clear();
+        key.extend_from_slice(match scanned {
+            Scanned::Borrowed(b)        => b,
+            Scanned::Scratch            => &self.scratch,
+            Scanned::Raw(start, len)    => &self.read.raw_since(start).unwrap_or(&[])[..len],
+        });
+
+        self.scanned_str(start, scanned)
+    }
+
+    fn depth(&self) -> usize { usize::from(RECURSION_LIMIT - self.remaining_depth) }
+
+    /// Record an array index where `parse_key` would record an object key, for [pointer](Self::pointer)s of ignored keys.
+    fn set_index(&mut self, index: usize) {
+        let depth = self.depth();
+        if self.keys.len() <= depth { self.keys.resize_with(depth + 1, Vec::new); }
+        let key = &mut self.keys[depth];
+        key.clear();
+        key.extend_from_slice(index.to_string().as_bytes());
+    }
+
+    /// Whether array indices need recording for [pointer](Self::pointer)s of ignored or duplicate keys
+    fn tracks_indices(&self) -> bool {
+        (self.ignored_keys.is_some() || self.settings.duplicate_keys == Some(DuplicateKeys::Collect)) && !self.ignoring
+    }
+
+    /// Forget the keys of the previous object at the current depth.
+    fn begin_object(&mut self) {
+        let depth = self.depth();
+        if self.seen.len() <= depth { self.seen.resize_with(depth + 1, SeenKeys::default); }
+        self.seen[depth].clear();
+    }
+
+    /// Remember the object key just parsed, spanning `range`, returning `true` if it's a duplicate to be handled per `policy`.
+    fn note_key(&mut self, range: Range<usize>, policy: DuplicateKeys) -> Result<bool> {
+        let depth = self.depth();
+        let key = self.keys.get(depth).map_or(&[][..], |k| &k[..]);
+        let first = match self.seen[depth].insert(key, range.clone()) {
+            Some(first) => first,
+            None        => return Ok(false),
+        };
+        match policy {
+            DuplicateKeys::Error    => return Err(self.fix_range(range.start, Error::duplicate_key(&self.current_key(), first))),
+            DuplicateKeys::Collect  => self.duplicate_keys.push(DuplicateKey::new(range, self.current_key().into_owned(), first, self.pointer())),
+            DuplicateKeys::KeepFirst | DuplicateKeys::KeepLast => {},
+        }
+        Ok(true)
+    }
+
+    /// Key offsets of entries repeated later in the object whose `{` is next, to skip in favor of the last value - latest first.
+    ///
+    /// The outermost struct's object is scanned ahead of time (and the reader rewound to its `{`), recording the earlier duplicates
+    /// of every object within it at once, so structs nested inside it don't need scanning again.
+    fn earlier_duplicates(&mut self) -> Vec<usize> {
+        let start = self.read.offset();
+        if let Some(earlier) = self.earlier.as_mut() { return earlier.remove(&start).unwrap_or_default() }
+
+        let ignoring = std::mem::replace(&mut self.ignoring, true);
+        let remaining_depth = self.remaining_depth;
+        let comments = self.comments.as_ref().map(Vec::len);
+        let mut earlier = HashMap::new();
+        self.read.pin(Some(start));
+        let scanned = self.scan_earlier_duplicates(&mut earlier);
+        self.read.rewind(start);
+        self.read.pin(None);
+        self.ignoring = ignoring;
+        self.remaining_depth = remaining_depth;
+        if let (Some(comments), Some(n)) = (self.comments.as_mut(), comments) { comments.truncate(n); }
+        if scanned.is_err() { return Vec::new() } // the error will be found again when parsing for real
+        let skip = earlier.remove(&start).unwrap_or_default();
+        self.earlier = Some(earlier);
+        skip
+    }
+
+    /// Skip the next value, recording the key offsets of earlier duplicates within each of its objects by the offset of the object's `{`.
+    fn scan_earlier_duplicates(&mut self, earlier: &mut HashMap<usize, Vec<usize>>) -> Result<()> {
+        match self.parse_whitespace()? {
+            Some(b'[') => {
+                self.enter()?;
+                let mut access = SeqAccess { de: &mut *self, index: 0 };
+                while access.has_next_element()? {
+                    access.index += 1;
+                    access.de.scan_earlier_duplicates(earlier)?;
+                }
+                self.leave();
+                self.end_seq()
+            },
+            Some(b'{') => {
+                let start = self.read.offset();
+                self.enter()?;
+                let mut last = HashMap::new();
+                let mut repeated = Vec::new();
+                let mut access = MapAccess::new(&mut *self, Vec::new());
+                while access.has_next_key()? {
+                    let key = access.de.read.offset();
+                    access.de.parse_key()?;
+                    let depth = access.de.depth();
+                    if let Some(previous) = last.insert(access.de.keys[depth].clone(), key) { repeated.push(previous); }
+                    access.de.parse_object_colon()?;
+                    access.de.scan_earlier_duplicates(earlier)?;
+                }
+                self.leave();
+                self.end_map()?;
+                if !repeated.is_empty() {
+                    repeated.sort_unstable_by(|a, b| b.cmp(a));
+                    earlier.insert(start, repeated);
+                }
+                Ok(())
+            },
+            _other => de::Deserialize::deserialize(&mut *self).map(|de::IgnoredAny| ()),
+        }
+    }
+
+    /// [JSON pointer](https://tools.ietf.org/html/rfc6901) of the current object key (or array index, if tracking ignored or duplicate keys.)
+    fn pointer(&self) -> String {
+        let mut pointer = String::new();
+        for key in self.keys.iter().take(self.depth() + 1).skip(1) {
+            pointer.push('/');
+            pointer.push_str(&String::from_utf8_lossy(key).replace('~', "~0").replace('/', "~1"));
+        }
+        pointer
+    }
+
+    /// The object key most recently parsed at the current depth
+    fn current_key(&self) -> Cow<'_, str> { String::from_utf8_lossy(self.keys.get(self.depth()).map_or(&[], |k| &k[..])) }
+
+    fn scanned_str(&self, start: usize, scanned: Scanned<'de>) -> Result<Reference<'de, '_, str>> {
+        let error = |_| self.error_at(start, "invalid unicode code point");
+        match self.scanned_bytes(scanned) {
+            Reference::Borrowed(b)  => std::str::from_utf8(b).map(Reference::Borrowed).map_err(error),
+            Reference::Copied(c)    => std::str::from_utf8(c).map(Reference::Copied).map_err(error),
+        }
+    }
+
+    /// Parse an escape sequence whose leading `\` was already consumed, appending it to `scratch`.
+    fn parse_escape(&mut self) -> Result<()> {
+        let ch = match self.read.peek() {
+            Some(ch) => ch,
+            None => return Err(self.peek_error("EOF while parsing a string")),
+        };
+        self.read.discard(1);
+        let unescaped = match ch {
+            b'"'    => b'"',
+            b'\\'   => b'\\',
+            b'/'    => b'/',
+            b'b'    => b'\x08',
+            b'f'    => b'\x0c',
+            b'n'    => b'\n',
+            b'r'    => b'\r',
+            b't'    => b'\t',
+            b'\''   if self.settings.allow_json5 => b'\'',
+            b'v'    if self.settings.allow_json5 => b'\x0b',
+            b'0'    if self.settings.allow_json5 && !matches!(self.read.peek(), Some(b'0' ..= b'9')) => b'\0',
+            b'0' ..= b'9' if self.settings.allow_json5 => return Err(self.error_at(self.read.offset() - 1, "invalid escape")), // `\0` followed by a digit, or `\1`...
+            b'\n'   if self.settings.allow_json5 => return Ok(()), // line continuation
+            b'\r'   if self.settings.allow_json5 => { if self.read.peek() == Some(b'\n') { self.read.discard(1) } return Ok(()) },
+            0xE2    if self.settings.allow_json5 && self.read.peek() == Some(0x80) && matches!(self.read.peek_at(1), Some(0xA8) | Some(0xA9)) => { self.read.discard(2); return Ok(()) }, // U+2028 / U+2029 line continuation
+            b'x'    if self.settings.allow_json5 => {
+                let mut n = 0u8;
+                for _ in 0..2 {
+                    let digit = match self.read.peek() {
+                        None                    => return Err(self.peek_error("EOF while parsing a string")),
+                        Some(ch @ b'0'..=b'9')  => ch - b'0',
+                        Some(ch @ b'a'..=b'f')  => ch - b'a' + 10,
+                        Some(ch @ b'A'..=b'F')  => ch - b'A' + 10,
+                        Some(_)                 => return Err(self.peek_error("invalid escape")),
+                    };
+                    n = (n << 4) | digit;
+                    self.read.discard(1);
+                }
+                let ch = char::from(n);
+                self.scratch.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+                return Ok(());
+            },
+            b'u'    => {
+                let start = self.read.offset() - 2;
+                let mut cp = u32::from(self.parse_hex4()?);
+                if (0xD800 ..= 0xDBFF).contains(&cp) {
+                    if self.read.peek() != Some(b'\\') || self.read.peek_at(1) != Some(b'u') {
+                        return Err(self.error_at(start, "lone leading surrogate in hex escape"));
+                    }
+                    self.read.discard(2);
+                    let low = u32::from(self.parse_hex4()?);
+                    if !(0xDC00 ..= 0xDFFF).contains(&low) { return Err(self.error_at(start, "lone leading surrogate in hex escape")) }
+                    cp = 0x10000 + ((cp - 0xD800) << 10) + (low - 0xDC00);
+                }
+                let ch = match std::char::from_u32(cp) {
+                    Some(ch) => ch,
+                    None => return Err(self.error_at(start, "invalid unicode code point")),
+                };
+                self.scratch.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+                return Ok(());
+            },
+            other if self.settings.allow_json5 => other, // any other character escapes to itself
+            _other  => return Err(self.error_at(self.read.offset() - 1, "invalid escape")),
+        };
+        self.scratch.push(unescaped);
+        Ok(())
+    }
+
+    fn parse_hex4(&mut self) -> Result<u16> {
+        let mut n = 0;
+        for _ in 0..4 {
+            let digit = match self.read.peek() {
+                None                    => return Err(self.peek_error("EOF while parsing a string")),
+                Some(ch @ b'0'..=b'9')  => ch - b'0',
+                Some(ch @ b'a'..=b'f')  => ch - b'a' + 10,
+                Some(ch @ b'A'..=b'F')  => ch - b'A' + 10,
+                Some(_)                 => return Err(self.peek_error("invalid escape")),
+            };
+            n = (n << 4) | u16::from(digit);
+            self.read.discard(1);
+        }
+        Ok(n)
+    }
+
+    fn enter(&mut self) -> Result<()> {
+        self.remaining_depth = match self.remaining_depth.checked_sub(1) {
+            Some(d) => d,
+            None    => return Err(self.peek_error("recursion limit exceeded")),
+        };
+        self.read.discard(1); // b'[' or b'{'
+        Ok(())
+    }
+
+    fn leave(&mut self) { self.remaining_depth += 1; }
+
+    fn end_seq(&mut self) -> Result<()> {
+        match self.parse_whitespace()? {
+            Some(b']') => {},
+            Some(b',') => {
+                self.read.discard(1);
+                match self.parse_whitespace()? {
+                    Some(b']') if self.settings.allow_trailing_comma => {},
+                    Some(b']') => return Err(self.peek_error("trailing comma")),
+                    _other     => return Err(self.peek_error("trailing characters")),
+                }
+            },
+            Some(_) => return Err(self.peek_error("trailing characters")),
+            None    => return Err(self.peek_error("EOF while parsing a list")),
+        }
+        self.read.discard(1);
+        self.end_value();
+        Ok(())
+    }
+
+    fn end_map(&mut self) -> Result<()> {
+        match self.parse_whitespace()? {
+            Some(b'}') => {},
+            Some(b',') => {
+                self.read.discard(1);
+                match self.parse_whitespace()? {
+                    Some(b'}') if self.settings.allow_trailing_comma => {},
+                    Some(b'}') => return Err(self.peek_error("trailing comma")),
+                    _other     => return Err(self.peek_error("trailing characters")),
+                }
+            },
+            Some(_) => return Err(self.peek_error("trailing characters")),
+            None    => return Err(self.peek_error("EOF while parsing an object")),
+        }
+        self.read.discard(1);
+        self.end_value();
+        Ok(())
+    }
+
+    /// [deserialize_any](de::Deserializer::deserialize_any), skipping object entries whose keys start at `skip` offsets (latest first.)
+    fn deserialize_value<V: Visitor<'de>>(&mut self, visitor: V, skip: Vec<usize>) -> Result<V::Value> {
+        let peek = match self.parse_whitespace()? {
+            Some(ch) => ch,
+            None => return Err(self.peek_error("EOF while parsing a value")),
+        };
+        let start = self.read.offset();
+
+        let value = match peek {
+            b'n' => { self.parse_ident(b"null")?;  visitor.visit_unit() },
+            b't' => { self.parse_ident(b"true")?;  visitor.visit_bool(true) },
+            b'f' => { self.parse_ident(b"false")?; visitor.visit_bool(false) },
+            b'-' | b'0' ..= b'9' => self.visit_number(visitor),
+            b'+' | b'.' | b'I' | b'N' if self.settings.allow_json5 => self.visit_number(visitor),
+            b'"' | b'\'' if self.is_quote(peek) => match self.parse_str()? {
+                Reference::Borrowed(s)  => visitor.visit_borrowed_str(s),
+                Reference::Copied(s)    => visitor.visit_str(s),
+            },
+            b'[' => {
+                self.enter()?;
+                let value = visitor.visit_seq(SeqAccess { de: &mut *self, index: 0 });
+                self.leave();
+                value.and_then(|value| self.end_seq().map(|()| value))
+            },
+            b'{' => {
+                self.enter()?;
+                let value = visitor.visit_map(MapAccess::new(&mut *self, skip));
+                self.leave();
+                value.and_then(|value| self.end_map().map(|()| value))
+            },
+            _other => return Err(self.peek_error("expected value")),
+        };
+
+        value.map_err(|err| self.fix_range(start, err))
+    }
+
+
+    fn visit_number<V: Visitor<'de>>(&mut self, visitor: V) -> Result<V::Value> {
+        match self.parse_number()? {
+            Number::U64(n) => visitor.visit_u64(n),
+            Number::I64(n) => visitor.visit_i64(n),
+            Number::F64(n) => visitor.visit_f64(n),
+        }
+    }
+}
+
+impl<'de, R: Read<'de>> de::Deserializer<'de> for &mut Deserializer<'de, R> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_value(visitor, Vec::new())
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.parse_whitespace()? {
+            Some(b'n') => {
+                let start = self.read.offset();
+                self.parse_ident(b"null")?;
+                visitor.visit_none().map_err(|err| self.fix_range(start, err))
+            },
+            _other => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V) -> Result<V::Value> {
+        if name == spanned::NAME {
+            visitor.visit_map(SpannedAccess { de: self, key: false, field: 0 })
+        } else if name == map::UNIQUE_KEYS {
+            // Duplicates are handled natively per Settings::duplicate_keys, or rejected by default
+            if self.parse_whitespace()? == Some(b'{') { self.unique = true; }
+            self.deserialize_any(visitor)
+        } else {
+            visitor.visit_newtype_struct(self)
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.parse_whitespace()? {
+            Some(ch) if self.is_quote(ch) => {
+                let start = self.read.offset();
+                let value = match self.parse_str_bytes()? {
+                    Reference::Borrowed(b)  => visitor.visit_borrowed_bytes(b),
+                    Reference::Copied(b)    => visitor.visit_bytes(b),
+                };
+                value.map_err(|err| self.fix_range(start, err))
+            },
+            _other => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        let peek = self.parse_whitespace()?;
+        let start = self.read.offset();
+        match peek {
+            Some(b'{') => {
+                self.enter()?;
+                let value = visitor.visit_enum(VariantAccess { de: &mut *self });
+                self.leave();
+                let value = value.map_err(|err| self.fix_range(start, err))?;
+                match self.parse_whitespace()? {
+                    Some(b'}') => { self.read.discard(1); self.end_value(); Ok(value) },
+                    Some(_)    => Err(self.peek_error("expected `}`")),
+                    None       => Err(self.peek_error("EOF while parsing an object")),
+                }
+            },
+            Some(ch) if self.is_quote(ch) => visitor.visit_enum(UnitVariantAccess { de: &mut *self }).map_err(|err| self.fix_range(start, err)),
+            Some(_) => Err(self.peek_error("expected value")),
+            None    => Err(self.peek_error("EOF while parsing a value")),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str, _fields: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        // Structs reject repeated fields themselves, so earlier values must be skipped to keep the last
+        match self.parse_whitespace()? {
+            Some(b'{') if !self.ignoring && matches!(self.settings.duplicate_keys, Some(DuplicateKeys::KeepLast) | Some(DuplicateKeys::Collect)) => {},
+            _other => return self.deserialize_value(visitor, Vec::new()),
+        }
+        let outermost = self.earlier.is_none();
+        let skip = self.earlier_duplicates();
+        let value = self.deserialize_value(visitor, skip);
+        if outermost { self.earlier = None; }
+        value
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let peek = self.parse_whitespace()?;
+
+        // Record the key of an ignored object entry, but not the keys nested inside its value
+        if let Some((value, key)) = self.entry.take() {
+            if value == self.read.offset() {
+                let ignored = IgnoredKey::new(key, self.current_key().into_owned(), self.pointer());
+                if let Some(keys) = self.ignored_keys.as_mut() { keys.push(ignored); }
+            }
+        }
+        let ignoring = std::mem::replace(&mut self.ignoring, true);
+
+        let value = match peek {
+            // Skip UTF-8 validation of strings nobody will look at, like serde_json does
+            Some(ch) if self.is_quote(ch) => {
+                let start = self.read.offset();
+                self.scan_str().and_then(|_| visitor.visit_unit().map_err(|err| self.fix_range(start, err)))
+            },
+            _other     => self.deserialize_any(visitor),
+        };
+        self.ignoring = ignoring;
+        value
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        seq tuple tuple_struct map identifier
+    }
+}
+
+
+
+struct SeqAccess<'a, 'de, R: Read<'de>> {
+    de:     &'a mut Deserializer<'de, R>,
+    index:  usize,
+}
+
+impl<'a, 'de, R: Read<'de>> SeqAccess<'a, 'de, R> {
+    /// Consume any `,` before the next element, returning `false` at the end of the list instead.
+    fn has_next_element(&mut self) -> Result<bool> {
+        match self.de.parse_whitespace()? {
+            Some(b']') => return Ok(false),
+            Some(b',') if self.index != 0 => {
+                self.de.read.discard(1);
+                match self.de.parse_whitespace()? {
+                    Some(b']') if self.de.settings.allow_trailing_comma => return Ok(false),
+                    Some(b']') => return Err(self.de.peek_error("trailing comma")),
+                    _other => {},
+                }
+            },
+            Some(_) if self.index == 0 => {},
+            Some(_) => return Err(self.de.peek_error("expected `,` or `]`")),
+            None    => return Err(self.de.peek_error("EOF while parsing a list")),
+        }
+        Ok(true)
+    }
+}
+
+impl<'de, 'a, R: Read<'de>> de::SeqAccess<'de> for SeqAccess<'a, 'de, R> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if !self.has_next_element()? { return Ok(None) }
+        let index = self.index;
+        self.index += 1;
+        if self.de.tracks_indices() { self.de.set_index(index); }
+        seed.deserialize(&mut *self.de).map(Some).map_err(|err| self.de.fix_position(err).at_index(index))
+    }
+}
+
+
+
+struct MapAccess<'a, 'de, R: Read<'de>> {
+    de:     &'a mut Deserializer<'de, R>,
+    first:  bool,
+    /// Range of the last key
+    key:    Range<usize>,
+    /// Offsets of keys whose entries should be skipped, latest first
+    skip:   Vec<usize>,
+    /// How to handle duplicate keys, if they're tracked at all
+    policy: Option<DuplicateKeys>,
+}
+
+impl<'a, 'de, R: Read<'de>> MapAccess<'a, 'de, R> {
+    fn new(de: &'a mut Deserializer<'de, R>, skip: Vec<usize>) -> Self {
+        let unique = std::mem::replace(&mut de.unique, false);
+        let policy = match de.settings.duplicate_keys {
+            _ if de.ignoring    => None,
+            None if unique      => Some(DuplicateKeys::Error),
+            policy              => policy,
+        };
+        if policy.is_some() { de.begin_object(); }
+        Self { de, first: true, key: 0 .. 0, skip, policy }
+    }
+
+    /// Consume any `,` before the next key, returning `false` at the end of the object instead.
+    fn has_next_key(&mut self) -> Result<bool> {
+        match self.de.parse_whitespace()? {
+            Some(b'}') => return Ok(false),
+            Some(b',') if !self.first => {
+                self.de.read.discard(1);
+                match self.de.parse_whitespace()? {
+                    Some(ch) if self.de.is_key_start(ch) => {},
+                    Some(b'}') if self.de.settings.allow_trailing_comma => return Ok(false),
+                    Some(b'}') => return Err(self.de.peek_error("trailing comma")),
+                    Some(_)    => return Err(self.de.peek_error("key must be a string")),
+                    None       => return Err(self.de.peek_error("EOF while parsing an object")),
+                }
+            },
+            Some(ch) if self.first && self.de.is_key_start(ch) => {},
+            Some(_) if self.first => return Err(self.de.peek_error("key must be a string")),
+            Some(_) => return Err(self.de.peek_error("expected `,` or `}`")),
+            None    => return Err(self.de.peek_error("EOF while parsing an object")),
+        }
+        self.first = false;
+        Ok(true)
+    }
+
+    /// Skip the value of an object entry whose key was just parsed.
+    fn skip_value(&mut self) -> Result<()> {
+        self.de.parse_object_colon()?;
+        de::Deserialize::deserialize(&mut *self.de).map(|de::IgnoredAny| ()).map_err(|err| self.de.fix_position(err).at_key(&self.de.current_key()))
+    }
+}
+
+impl<'de, 'a, R: Read<'de>> de::MapAccess<'de> for MapAccess<'a, 'de, R> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        let start = loop {
+            if !self.has_next_key()? { return Ok(None) }
+            let start = self.de.read.offset();
+            if self.skip.last() == Some(&start) {
+                // An earlier duplicate, skipped in favor of the last value
+                self.skip.pop();
+                self.de.parse_key()?;
+                if self.policy == Some(DuplicateKeys::Collect) { self.de.note_key(start .. self.de.read.offset(), DuplicateKeys::Collect)?; }
+                self.skip_value()?;
+            } else if self.policy == Some(DuplicateKeys::KeepFirst) {
+                // Check the key before the seed consumes it, so a later duplicate can be skipped in favor of the first value
+                self.de.read.pin(Some(start));
+                let duplicate = self.de.parse_key().map(|_| ()).and_then(|()| self.de.note_key(start .. self.de.read.offset(), DuplicateKeys::KeepFirst));
+                self.de.read.pin(None);
+                if duplicate? {
+                    self.skip_value()?;
+                } else {
+                    self.de.read.rewind(start);
+                    break start;
+                }
+            } else {
+                break start;
+            }
+        };
+        let key = seed.deserialize(MapKey { de: &mut *self.de }).map_err(|err| self.de.fix_position(err))?;
+        self.key = start .. self.de.read.offset();
+        if let Some(policy @ DuplicateKeys::Error) | Some(policy @ DuplicateKeys::Collect) = self.policy { self.de.note_key(self.key.clone(), policy)?; }
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        self.de.parse_object_colon()?;
+        if self.de.ignored_keys.is_some() && !self.de.ignoring {
+            self.de.parse_whitespace()?;
+            self.de.entry = Some((self.de.read.offset(), self.key.clone()));
+        }
+        seed.deserialize(&mut *self.de).map_err(|err| self.de.fix_position(err).at_key(&self.de.current_key()))
+    }
+}
+
+impl<'de, R: Read<'de>> Deserializer<'de, R> {
+    /// `"`, or JSON5's `'`
+    fn is_quote(&self, ch: u8) -> bool {
+        ch == b'"' || (ch == b'\'' && self.settings.allow_json5)
+    }
+
+    /// A quote, or the start of a JSON5 identifier
+    fn is_key_start(&self, ch: u8) -> bool {
+        self.is_quote(ch) || (self.settings.allow_json5 && (ch == b'$' || ch == b'_' || ch == b'\\' || ch.is_ascii_alphabetic() || ch >= 0x80))
+    }
+
+    fn parse_object_colon(&mut self) -> Result<()> {
+        match self.parse_whitespace()? {
+            Some(b':') => { self.read.discard(1); Ok(()) },
+            Some(_)    => Err(self.peek_error("expected `:`")),
+            None       => Err(self.peek_error("EOF while parsing an object")),
+        }
+    }
+}
+
+
+
+/// Error-tolerant parsing for [from_str_partial](crate::from_str_partial)
+impl<'de, R: Read<'de>> Deserializer<'de, R> {
+    /// Parse the entire input into a tree, even if it isn't valid, along with every syntax error found.
+    pub(crate) fn parse_partial(&mut self) -> (spanned::Value, Vec<Error>) {
+        let mut errors = Vec::new();
+        let mut pointer = String::new();
+        let value = self.partial_value(&mut errors, &mut pointer);
+        if self.partial_whitespace(&mut errors, &pointer).is_some() {
+            let start = self.read.offset();
+            while self.read.peek().is_some() {
+                let n = self.read.peek_buffered().len();
+                self.read.discard(n);
+            }
+            errors.push(self.error_at(start, "trailing characters"));
+        }
+        if let Some(err) = self.read.take_error() { errors.push(Error::io(err)); }
+        (value, errors)
+    }
+
+    fn partial_whitespace(&mut self, errors: &mut Vec<Error>, pointer: &str) -> Option<u8> {
+        match self.parse_whitespace() {
+            Ok(peek)    => peek,
+            Err(err)    => { errors.push(err.with_pointer(pointer)); None }, // unterminated comment
+        }
+    }
+
+    /// Skip the rest of a malformed token, up to whitespace or punctuation.
+    fn skip_token(&mut self) {
+        while let Some(ch) = self.read.peek() {
+            if matches!(ch, b' ' | b'\n' | b'\t' | b'\r' | b'[' | b']' | b'{' | b'}' | b',' | b':' | b'"' | b'\'') { break }
+            self.read.discard(1);
+        }
+    }
+
+    /// Skip the rest of a malformed string, through its closing quote if on the same line.
+    fn skip_string(&mut self, quote: u8) {
+        while let Some(ch) = self.read.peek() {
+            match ch {
+                b'\n' | b'\r'           => break,
+                b'\\'                   => { self.read.discard(1); if self.read.peek().is_some() { self.read.discard(1) } },
+                ch if ch == quote       => { self.read.discard(1); break },
+                _other                  => self.read.discard(1),
+            }
+        }
+    }
+
+    /// Parse a value, skipping any unexpected tokens first.
+    /// Values that are missing or malformed become a `null` placeholder with an empty span.
+    fn partial_value(&mut self, errors: &mut Vec<Error>, pointer: &mut String) -> spanned::Value {
+        let mut skipped = false;
+        loop {
+            let peek = self.partial_whitespace(errors, pointer);
+            let start = self.read.offset();
+            let placeholder = Spanned { start, end: start, value: Value::Null };
+            let spanned = |de: &Self, value| Spanned { start, end: de.read.offset(), value };
+            match peek {
+                None | Some(b']') | Some(b'}') | Some(b',') => {
+                    if !skipped { errors.push(self.peek_error(if peek.is_none() { "EOF while parsing a value" } else { "expected value" }).with_pointer(pointer)); }
+                    return placeholder;
+                },
+                Some(b'[') | Some(b'{') => return self.partial_container(errors, pointer),
+                Some(ch @ b'n') | Some(ch @ b't') | Some(ch @ b'f') => {
+                    let (ident, value) = match ch { b'n' => (&b"null"[..], Value::Null), b't' => (&b"true"[..], Value::Bool(true)), _ => (&b"false"[..], Value::Bool(false)) };
+                    match self.parse_ident(ident) {
+                        Ok(())  => return spanned(self, value),
+                        Err(err) if !skipped => { errors.push(err.with_pointer(pointer)); skipped = true },
+                        Err(_)  => {},
+                    }
+                },
+                Some(b'-') | Some(b'0' ..= b'9') | Some(b'+') | Some(b'.') | Some(b'I') | Some(b'N') if self.settings.allow_json5 || matches!(peek, Some(b'-') | Some(b'0' ..= b'9')) => {
+                    match self.parse_number() {
+                        Ok(Number::U64(n))  => return spanned(self, Value::Number(n.into())),
+                        Ok(Number::I64(n))  => return spanned(self, Value::Number(n.into())),
+                        Ok(Number::F64(n))  => return spanned(self, serde_json::Number::from_f64(n).map_or(Value::Null, Value::Number)),
+                        Err(err)            => {
+                            self.skip_token();
+                            errors.push(err.with_pointer(pointer));
+                            return placeholder;
+                        },
+                    }
+                },
+                Some(quote) if self.is_quote(quote) => match self.parse_str() {
+                    Ok(s)   => { let s = String::from(&*s); return spanned(self, Value::String(s)) },
+                    Err(err) => {
+                        self.skip_string(quote);
+                        errors.push(err.with_pointer(pointer));
+                        return placeholder;
+                    },
+                },
+                Some(_) => {},
+            }
+
+            // Something unexpected - skip it, and try again
+            if self.read.offset() == start { self.read.discard(1); }
+            self.skip_token();
+            if !skipped { errors.push(self.error_at(start, "expected value").with_pointer(pointer)); }
+            skipped = true;
+        }
+    }
+
+    fn partial_container(&mut self, errors: &mut Vec<Error>, pointer: &mut String) -> spanned::Value {
+        let start = self.read.offset();
+        let object = self.read.peek() == Some(b'{');
+        let (close, eof) = if object { (b'}', "EOF while parsing an object") } else { (b']', "EOF while parsing a list") };
+        if let Err(err) = self.enter() {
+            errors.push(err.with_pointer(pointer));
+            self.read.discard(1);
+            return Spanned { start, end: start, value: Value::Null };
+        }
+        if object { self.begin_object(); }
+
+        let parent = pointer.len();
+        let mut array = Vec::new();
+        let mut map = Map::new();
+        let mut first = true;
+        let mut comma = false;
+        loop {
+            let peek = self.partial_whitespace(errors, &pointer[.. parent]);
+            let missing = match peek {
+                Some(ch) if ch == close => {
+                    if comma && !self.settings.allow_trailing_comma { errors.push(self.peek_error("trailing comma").with_pointer(&pointer[.. parent])); }
+                    self.read.discard(1);
+                    break;
+                },
+                None if comma && !object                => Some("EOF while parsing a value"),
+                None                                    => Some(eof),
+                Some(b']') | Some(b'}') if first || comma => Some(if object { "key must be a string" } else { "expected value" }),
+                Some(b']') | Some(b'}')                 => Some(if object { "expected `,` or `}`" } else { "expected `,` or `]`" }),
+                Some(_) if first || comma               => None,
+                Some(_)                                 => {
+                    errors.push(self.peek_error(if object { "expected `,` or `}`" } else { "expected `,` or `]`" }).with_pointer(&pointer[.. parent]));
+                    None
+                },
+            };
+            if let Some(missing) = missing {
+                // EOF or mismatched brackets: leave any `]` or `}` for the parent
+                errors.push(self.peek_error(missing).with_pointer(&pointer[.. parent]));
+                break;
+            }
+
+            first = false;
+            pointer.truncate(parent);
+            if !object {
+                pointer.push_str(&format!("/{}", array.len()));
+                array.push(self.partial_value(errors, pointer));
+            } else {
+                let key_start = self.read.offset();
+                let key = match self.partial_whitespace(errors, pointer) {
+                    Some(ch) if self.is_key_start(ch) => match self.parse_key() {
+                        Ok(key) => Some(String::from(&*key)),
+                        Err(err) => {
+                            if self.is_quote(ch) { self.skip_string(ch) } else { self.skip_token() }
+                            errors.push(err.with_pointer(pointer));
+                            None
+                        },
+                    },
+                    Some(b',') | Some(b':') => {
+                        errors.push(self.peek_error("key must be a string").with_pointer(pointer));
+                        None
+                    },
+                    _other => {
+                        if self.read.offset() == key_start { self.read.discard(1); }
+                        self.skip_token();
+                        errors.push(self.error_at(key_start, "key must be a string").with_pointer(pointer));
+                        None
+                    },
+                };
+                let key_end = self.read.offset();
+                if let Some(key) = key.as_ref() { pointer.push('/'); pointer.push_str(&key.replace('~', "~0").replace('/', "~1")); }
+                let policy = self.settings.duplicate_keys.unwrap_or(DuplicateKeys::Error);
+                let keep = key.is_some() && match self.note_key(key_start .. key_end, policy) {
+                    Ok(duplicate)   => !duplicate || policy != DuplicateKeys::KeepFirst,
+                    Err(err)        => { errors.push(err.with_pointer(pointer)); false },
+                };
+
+                let parsed = key.is_some();
+                let key = key.filter(|_| keep).map(|key| Spanned { start: key_start, end: key_end, value: key });
+                match self.partial_whitespace(errors, pointer) {
+                    Some(b':')                                  => { self.read.discard(1); },
+                    Some(b',') if !parsed                       => { self.read.discard(1); comma = true; continue },
+                    None => {
+                        if parsed { errors.push(self.peek_error(eof).with_pointer(pointer)); }
+                        let end = self.read.offset();
+                        if let Some(key) = key { map.insert(key, Spanned { start: end, end, value: Value::Null }); }
+                        break;
+                    },
+                    _other if !parsed                           => {},
+                    Some(_)                                     => errors.push(self.peek_error("expected `:`").with_pointer(pointer)),
+                }
+                let value = self.partial_value(errors, pointer);
+                if let Some(key) = key { map.insert(key, value); }
+            }
+
+            comma = self.partial_whitespace(errors, &pointer[.. parent]) == Some(b',');
+            if comma { self.read.discard(1); }
+        }
+        pointer.truncate(parent);
+        self.leave();
+        let value = if object { Value::Object(map) } else { Value::Array(array) };
+        Spanned { start, end: self.read.offset(), value }
+    }
+}
+
+
+
+/// Lexing for [Tokenizer](crate::Tokenizer)
+impl<'de, R: Read<'de>> Deserializer<'de, R> {
+    /// Scan the next token, or `None` at EOF.  Malformed tokens are skipped after returning their error.
+    pub(crate) fn next_token(&mut self) -> Option<Result<Token>> {
+        let peek = self.read.peek()?;
+        let start = self.read.offset();
+        let token = |de: &Self, kind| Some(Ok(Token::new(kind, start .. de.read.offset())));
+        let punctuation = match peek {
+            b'[' => Some(TokenKind::BeginArray),
+            b']' => Some(TokenKind::EndArray),
+            b'{' => Some(TokenKind::BeginObject),
+            b'}' => Some(TokenKind::EndObject),
+            b':' => Some(TokenKind::Colon),
+            b',' => Some(TokenKind::Comma),
+            _other => None,
+        };
+        if let Some(kind) = punctuation {
+            self.read.discard(1);
+            return token(self, kind);
+        }
+
+        let result = match peek {
+            _whitespace if matches!(peek, b' ' | b'\n' | b'\t' | b'\r') || self.json5_whitespace_len() != 0 => {
+                loop {
+                    match self.read.peek() {
+                        Some(b' ') | Some(b'\n') | Some(b'\t') | Some(b'\r') => self.read.discard(1),
+                        _other => match self.json5_whitespace_len() {
+                            0 => break,
+                            n => self.read.discard(n),
+                        },
+                    }
+                }
+                Ok(TokenKind::Whitespace)
+            },
+            b'/' if self.settings.allow_comments && self.read.peek_at(1) == Some(b'/') => {
+                loop {
+                    match self.read.peek() {
+                        None | Some(b'\n') => break,
+                        Some(b'\r') if self.read.peek_at(1) == Some(b'\n') => break,
+                        Some(_) => self.read.discard(1),
+                    }
+                }
+                Ok(TokenKind::LineComment)
+            },
+            b'/' if self.settings.allow_comments && self.read.peek_at(1) == Some(b'*') => {
+                self.read.discard(2);
+                loop {
+                    match self.read.peek() {
+                        None        => break Err(self.error_at(start, "EOF while parsing a comment")),
+                        Some(b'*') if self.read.peek_at(1) == Some(b'/') => { self.read.discard(2); break Ok(TokenKind::BlockComment) },
+                        Some(_)     => self.read.discard(1),
+                    }
+                }
+            },
+            ch if self.is_quote(ch) => match self.scan_str() {
+                Ok(_)   => Ok(TokenKind::String),
+                Err(err) => { self.skip_string(ch); Err(err) },
+            },
+            b'-' | b'0' ..= b'9' => self.parse_number().map(|_| TokenKind::Number),
+            b'+' | b'.' if self.settings.allow_json5 => self.parse_number().map(|_| TokenKind::Number),
+            ch if ch == b'$' || ch == b'_' || ch.is_ascii_alphabetic() || ch >= 0x80 => {
+                while let Some(ch) = self.read.peek() {
+                    if !(ch == b'$' || ch == b'_' || ch.is_ascii_alphanumeric() || ch >= 0x80) || self.json5_whitespace_len() != 0 { break }
+                    self.read.discard(1);
+                }
+                match self.read.raw_since(start).unwrap_or(&[]) {
+                    b"null"                                         => Ok(TokenKind::Null),
+                    b"true"                                         => Ok(TokenKind::True),
+                    b"false"                                        => Ok(TokenKind::False),
+                    b"Infinity" | b"NaN" if self.settings.allow_json5 => Ok(TokenKind::Number),
+                    _ident if self.settings.allow_json5             => Ok(TokenKind::Identifier),
+                    _ident                                          => Err(self.error_at(start, "expected value")),
+                }
+            },
+            _other => {
+                self.read.discard(1);
+                while matches!(self.read.peek(), Some(0x80 ..= 0xBF)) { self.read.discard(1); } // rest of the character
+                Err(self.error_at(start, "unexpected character"))
+            },
+        };
+
+        match result {
+            Ok(kind) => token(self, kind),
+            Err(err) => {
+                if self.read.offset() == start { self.read.discard(1); }
+                self.skip_token();
+                Some(Err(self.fix_range(start, err)))
+            },
+        }
+    }
+}
+
+
+/// Object keys are always strings in JSON, but may be deserialized as numbers or bools (e.g. `HashMap<u32, _>`.)
+struct MapKey<'a, 'de, R: Read<'de>> {
+    de:     &'a mut Deserializer<'de, R>,
+}
+
+macro_rules! deserialize_parsed_key {
+    ($( $method:ident => $visit:ident, )*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+            let start = self.de.read.offset();
+            let value = match self.de.parse_key()? {
+                Reference::Borrowed(s)  => s.parse().map_err(|_| de::Error::invalid_type(Unexpected::Str(s), &visitor)).and_then(|v| visitor.$visit(v)),
+                Reference::Copied(s)    => s.parse().map_err(|_| de::Error::invalid_type(Unexpected::Str(s), &visitor)).and_then(|v| visitor.$visit(v)),
+            };
+            value.map_err(|err| self.de.fix_range(start, err))
+        }
+    )*};
+}
+
+impl<'de, 'a, R: Read<'de>> de::Deserializer<'de> for MapKey<'a, 'de, R> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let start = self.de.read.offset();
+        let value = match self.de.parse_key()? {
+            Reference::Borrowed(s)  => visitor.visit_borrowed_str(s),
+            Reference::Copied(s)    => visitor.visit_str(s),
+        };
+        value.map_err(|err| self.de.fix_range(start, err))
+    }
+
+    deserialize_parsed_key! {
+        deserialize_bool    => visit_bool,
+        deserialize_i8      => visit_i8,
+        deserialize_i16     => visit_i16,
+        deserialize_i32     => visit_i32,
+        deserialize_i64     => visit_i64,
+        deserialize_i128    => visit_i128,
+        deserialize_u8      => visit_u8,
+        deserialize_u16     => visit_u16,
+        deserialize_u32     => visit_u32,
+        deserialize_u64     => visit_u64,
+        deserialize_u128    => visit_u128,
+        deserialize_f32     => visit_f32,
+        deserialize_f64     => visit_f64,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> { visitor.visit_some(self) }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V) -> Result<V::Value> {
+        if name == spanned::NAME {
+            visitor.visit_map(SpannedAccess { de: self.de, key: true, field: 0 })
+        } else {
+            visitor.visit_newtype_struct(self)
+        }
+    }
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let start = self.de.read.offset();
+        let value = match self.de.parse_key()? {
+            Reference::Borrowed(s)  => visitor.visit_borrowed_bytes(s.as_bytes()),
+            Reference::Copied(s)    => visitor.visit_bytes(s.as_bytes()),
+        };
+        value.map_err(|err| self.de.fix_range(start, err))
+    }
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> { self.deserialize_bytes(visitor) }
+    fn deserialize_enum<V: Visitor<'de>>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value> { self.de.deserialize_enum(name, variants, visitor) }
+
+    serde::forward_to_deserialize_any! {
+        char str string unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+
+
+/// `{"Variant": ...}`
+struct VariantAccess<'a, 'de, R: Read<'de>> {
+    de: &'a mut Deserializer<'de, R>,
+}
+
+impl<'de, 'a, R: Read<'de>> de::EnumAccess<'de> for VariantAccess<'a, 'de, R> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        match self.de.parse_whitespace()? {
+            Some(ch) if self.de.is_key_start(ch) => {},
+            Some(_)    => return Err(self.de.peek_error("key must be a string")),
+            None       => return Err(self.de.peek_error("EOF while parsing an object")),
+        }
+        let variant = seed.deserialize(MapKey { de: &mut *self.de })?;
+        self.de.parse_object_colon()?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'a, R: Read<'de>> de::VariantAccess<'de> for VariantAccess<'a, 'de, R> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        de::Deserialize::deserialize(&mut *self.de).map_err(|err| err.at_key(&self.de.current_key()))
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(&mut *self.de).map_err(|err| err.at_key(&self.de.current_key()))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(&mut *self.de, visitor).map_err(|err| err.at_key(&self.de.current_key()))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_struct(&mut *self.de, "", fields, visitor).map_err(|err| err.at_key(&self.de.current_key()))
+    }
+}
+
+
+
+/// `"Variant"`
+struct UnitVariantAccess<'a, 'de, R: Read<'de>> {
+    de: &'a mut Deserializer<'de, R>,
+}
+
+impl<'de, 'a, R: Read<'de>> de::EnumAccess<'de> for UnitVariantAccess<'a, 'de, R> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        // Unlike `{"Variant": ...}`, the name isn't an object key: keep the key of the value being deserialized for pointers
+        let depth = self.de.depth();
+        let key = self.de.keys.get_mut(depth).map(std::mem::take);
+        let variant = seed.deserialize(MapKey { de: &mut *self.de });
+        if let Some(slot) = self.de.keys.get_mut(depth) { *slot = key.unwrap_or_default(); }
+        Ok((variant?, self))
+    }
+}
+
+impl<'de, 'a, R: Read<'de>> de::VariantAccess<'de> for UnitVariantAccess<'a, 'de, R> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> { Ok(()) }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, _seed: T) -> Result<T::Value> {
+        Err(de::Error::invalid_type(Unexpected::UnitVariant, &"newtype variant"))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, _visitor: V) -> Result<V::Value> {
+        Err(de::Error::invalid_type(Unexpected::UnitVariant, &"tuple variant"))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], _visitor: V) -> Result<V::Value> {
+        Err(de::Error::invalid_type(Unexpected::UnitVariant, &"struct variant"))
+    }
+}
+
+
+
+/// Feeds a [Spanned](crate::Spanned) its start, value, and end as if they were fields of a map.
+struct SpannedAccess<'a, 'de, R: Read<'de>> {
+    de:     &'a mut Deserializer<'de, R>,
+    /// Deserialize the value as an object key (see [MapKey])
+    key:    bool,
+    field:  usize,
+}
+
+impl<'de, 'a, R: Read<'de>> de::MapAccess<'de> for SpannedAccess<'a, 'de, R> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match spanned::FIELDS.get(self.field) {
+            Some(field) => seed.deserialize(BorrowedStrDeserializer::new(field)).map(Some),
+            None        => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        self.field += 1;
+        match self.field {
+            1       => {
+                // Only parse after being visited, so errors are never mistaken for a missing field (see Spanned::deserialize)
+                if !self.key { self.de.parse_whitespace()?; }
+                seed.deserialize((self.de.read.offset() as u64).into_deserializer())
+            },
+            2       => if self.key {
+                seed.deserialize(MapKey { de: &mut *self.de })
+            } else {
+                seed.deserialize(&mut *self.de)
+            },
+            _end    => seed.deserialize((self.de.end as u64).into_deserializer()),
+        }
+    }
+}
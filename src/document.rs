@@ -0,0 +1,247 @@
+use crate::{spanned, Comments, Error, Result, Settings, Value};
+use crate::error::Category;
+
+use serde::Serialize;
+
+use std::cmp::Reverse;
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+
+
+
+/// An editable json document, which keeps everything that isn't edited - whitespace, comments, key order, trailing commas - byte for byte.
+///
+/// Values are addressed by [JSON pointer](https://tools.ietf.org/html/rfc6901), and new values are written as compact json.
+/// The document is re-parsed after each edit, so [value](Self::value) spans are always up to date.
+///
+/// ```
+/// # use json_spanned_value as jsv;
+/// let json = "{\n    // Where to listen\n    \"port\": 80,\n    \"hosts\": [\"a\", \"b\"], // TODO: more\n}\n";
+/// let settings = jsv::Settings { allow_comments: true, allow_trailing_comma: true, ..Default::default() };
+/// let mut doc = jsv::Document::parse(json, &settings).unwrap();
+///
+/// doc.set("/port", 8080).unwrap();
+/// doc.insert("/hosts/-", "c").unwrap();
+/// doc.rename("/hosts", "servers").unwrap();
+/// doc.insert("/verbose", true).unwrap();
+///
+/// assert_eq!(doc.to_string(), "{\n    // Where to listen\n    \"port\": 8080,\n    \"servers\": [\"a\", \"b\", \"c\"], // TODO: more\n    \"verbose\": true,\n}\n");
+/// ```
+#[derive(Debug)]
+pub struct Document {
+    text:       String,
+    settings:   Settings,
+    value:      spanned::Value,
+    comments:   Comments,
+}
+
+impl Document {
+    /// Parse `text` as a document.  Comments are only allowed if [Settings::allow_comments] (or [allow_json5](Settings::allow_json5)) is set.
+    pub fn parse(text: impl Into<String>, settings: &Settings) -> Result<Self> {
+        let text = text.into();
+        let (value, comments) = crate::from_str_with_comments(&text, settings)?;
+        Ok(Self { text, settings: *settings, value, comments })
+    }
+
+    /// The document's text, including any edits.
+    pub fn as_str(&self) -> &str { &self.text }
+
+    /// The document's text, including any edits.
+    pub fn into_string(self) -> String { self.text }
+
+    /// The document's root value, with spans into [as_str](Self::as_str).
+    pub fn value(&self) -> &spanned::Value { &self.value }
+
+    /// The document's comments.
+    pub fn comments(&self) -> &Comments { &self.comments }
+
+    /// Replace the existing value at `pointer`.
+    pub fn set<T: Serialize>(&mut self, pointer: &str, value: T) -> Result<()> {
+        let range = self.get(pointer)?.range();
+        let json = serde_json::to_string(&value)?;
+        self.splice(vec![(range, json)])
+    }
+
+    /// Add a new object member, or insert an array element, at `pointer`.
+    /// Array indices may be at most the length of the array, which (like `-`) appends.
+    /// Members are added after the last existing member.
+    pub fn insert<T: Serialize>(&mut self, pointer: &str, value: T) -> Result<()> {
+        let (parent, token) = split(pointer)?;
+        let container = self.get(parent)?;
+        let items = items(container);
+        let (json, index) = match container.get_ref() {
+            Value::Object(o) => {
+                if o.contains_key(token.as_str()) { return Err(error(pointer, "already exists")) }
+                (format!("{}: {}", serde_json::to_string(&token)?, serde_json::to_string(&value)?), items.len())
+            },
+            Value::Array(a) => match token.as_str() {
+                "-"     => (serde_json::to_string(&value)?, a.len()),
+                index   => match index.parse::<usize>() {
+                    Ok(i) if i <= a.len()   => (serde_json::to_string(&value)?, i),
+                    _other                  => return Err(error(pointer, "is not a valid array index")),
+                },
+            },
+            _scalar => return Err(error(parent, "is not an array or object")),
+        };
+
+        let open = container.start() + 1;
+        let close = container.end() - 1;
+        let separator = match items.first() {
+            Some(first) if self.text[open .. first.start].contains('\n') => format!("\n{}", self.indent_of(first.start)),
+            _inline => " ".to_string(),
+        };
+
+        let edits = match (items.get(index), items.last()) {
+            (Some(next), _)     => vec![(next.start .. next.start, format!("{},{}", json, separator))],
+            (None, None)        => vec![(open .. open, json)],
+            (None, Some(last))  => match self.comma_after(last.end, close) {
+                // Trailing comma style: keep it, after the new item
+                Some(comma) if separator.starts_with('\n') => {
+                    let eol = self.newline_after(comma, close).unwrap_or(close);
+                    vec![(eol .. eol, format!("{}{},", separator, json))]
+                },
+                Some(comma) => vec![(comma + 1 .. comma + 1, format!("{}{},", separator, json))],
+                None if separator.starts_with('\n') => {
+                    // Insert after any comments trailing the last item
+                    let eol = self.newline_after(last.end, close).unwrap_or(close);
+                    vec![(last.end .. last.end, ",".to_string()), (eol .. eol, format!("{}{}", separator, json))]
+                },
+                None => vec![(last.end .. last.end, format!(",{}{}", separator, json))],
+            },
+        };
+        self.splice(edits)
+    }
+
+    /// Remove the object member or array element at `pointer`, along with its separating comma and any comments attached to it.
+    pub fn remove(&mut self, pointer: &str) -> Result<()> {
+        let (parent, _token) = split(pointer)?;
+        let value = self.get(pointer)?.range();
+        let container = self.get(parent)?;
+        let (open, close) = (container.start() + 1, container.end() - 1);
+        let items = items(container);
+        let index = items.iter().position(|item| item.end == value.end).ok_or_else(|| error(pointer, "is not an array element or object member"))?;
+        let item = &items[index];
+
+        let comma = self.comma_after(item.end, close);
+        let mut start = self.comments.leading(pointer).iter().map(|c| c.range().start).fold(item.start, usize::min);
+        let mut end = comma.map_or(item.end, |c| c + 1);
+        end = self.comments.trailing(pointer).iter().map(|c| c.range().end).fold(end, usize::max);
+
+        let mut edits = Vec::new();
+        let line_start = self.text[open .. start].rfind('\n').map(|n| open + n + 1);
+        let line_end = self.text[end .. close].find('\n').map(|n| end + n + 1);
+        match (line_start, line_end) {
+            // Alone on its line(s): remove them entirely
+            (Some(ls), Some(le)) if self.text[ls .. start].trim().is_empty() && self.text[end .. le].trim().is_empty() => {
+                start = ls;
+                end = le;
+                if comma.is_none() && index > 0 {
+                    // The previous item was followed by this one, but now it's last
+                    if let Some(prev_comma) = self.comma_after(items[index - 1].end, close) { edits.push((prev_comma .. prev_comma + 1, String::new())); }
+                }
+            },
+            _inline => match comma {
+                Some(_) if index + 1 < items.len()  => end = self.text[end .. close].find(|ch: char| !ch.is_ascii_whitespace()).map_or(close, |n| end + n),
+                None if index > 0                   => start = items[index - 1].end,
+                _other                              => {},
+            },
+        }
+        edits.push((start .. end, String::new()));
+        self.splice(edits)
+    }
+
+    /// Rename the object member at `pointer` to `key`.
+    pub fn rename(&mut self, pointer: &str, key: &str) -> Result<()> {
+        let (parent, token) = split(pointer)?;
+        let object = self.get(parent)?;
+        let o = object.as_object().ok_or_else(|| error(parent, "is not an object"))?;
+        let (old, _value) = o.get_key_value(token.as_str()).ok_or_else(|| error(pointer, "not found"))?;
+        if o.contains_key(key) { return Err(error(pointer, format_args!("can't be renamed to {:?}, which already exists", key))) }
+        let range = old.range();
+        self.splice(vec![(range, serde_json::to_string(key)?)])
+    }
+
+    fn get(&self, pointer: &str) -> Result<&spanned::Value> {
+        self.value.pointer(pointer).ok_or_else(|| error(pointer, "not found"))
+    }
+
+    /// The whitespace between the start of `offset`'s line and `offset`.
+    fn indent_of(&self, offset: usize) -> &str {
+        let line = self.text[.. offset].rfind('\n').map_or(0, |n| n + 1);
+        let indent = &self.text[line .. offset];
+        &indent[.. indent.len() - indent.trim_start().len()]
+    }
+
+    /// The offset of the `,` after `offset` (skipping whitespace and comments), if there is one before `close`.
+    fn comma_after(&self, offset: usize, close: usize) -> Option<usize> {
+        let mut rest = &self.text[offset .. close];
+        loop {
+            let trimmed = rest.trim_start();
+            rest = if let Some(comment) = trimmed.strip_prefix("//") {
+                comment.find('\n').map_or("", |n| &comment[n..])
+            } else if let Some(comment) = trimmed.strip_prefix("/*") {
+                comment.find("*/").map_or("", |n| &comment[n + 2 ..])
+            } else if trimmed.starts_with(',') {
+                return Some(close - trimmed.len());
+            } else {
+                return None;
+            };
+        }
+    }
+
+    /// The offset of the first `\n` after `offset` that isn't inside a block comment, if there is one before `close`.
+    fn newline_after(&self, offset: usize, close: usize) -> Option<usize> {
+        let mut from = offset;
+        loop {
+            let newline = from + self.text[from .. close].find('\n')?;
+            match self.comments.all().iter().find(|c| c.range().contains(&newline)) {
+                Some(comment)   => from = comment.range().end,
+                None            => return Some(newline),
+            }
+        }
+    }
+
+    /// Replace ranges of the text, and re-parse it.  The document is left untouched if that fails.
+    /// Insertions at the same offset end up in the order given.
+    fn splice(&mut self, edits: Vec<(Range<usize>, String)>) -> Result<()> {
+        let mut text = self.text.clone();
+        let mut edits = edits.into_iter().enumerate().collect::<Vec<_>>();
+        edits.sort_by_key(|(i, (range, _))| Reverse((range.start, *i)));
+        for (_, (range, with)) in edits { text.replace_range(range, &with); }
+        *self = Self::parse(text, &self.settings)?;
+        Ok(())
+    }
+}
+
+impl Display for Document {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { fmt.write_str(&self.text) }
+}
+
+/// The ranges of the members (from key through value) or elements of `container`, in the order they appear.
+fn items(container: &spanned::Value) -> Vec<Range<usize>> {
+    let mut items = match container.get_ref() {
+        Value::Array(a)     => a.iter().map(|v| v.range()).collect::<Vec<_>>(),
+        Value::Object(o)    => o.iter().map(|(k, v)| k.start() .. v.end()).collect(),
+        _scalar             => Vec::new(),
+    };
+    items.sort_by_key(|item| item.start);
+    items
+}
+
+/// Split `pointer` into the pointer of its parent, and its last (unescaped) token.
+fn split(pointer: &str) -> Result<(&str, String)> {
+    match pointer.rfind('/') {
+        Some(n)                     => Ok((&pointer[.. n], pointer[n + 1 ..].replace("~1", "/").replace("~0", "~"))),
+        None if pointer.is_empty()  => Err(error(pointer, "has no parent")),
+        None                        => Err(error(pointer, "is not a JSON pointer")),
+    }
+}
+
+fn error(pointer: &str, msg: impl Display) -> Error {
+    let err = if pointer.is_empty() {
+        Error::new(Category::Data, format_args!("the root value {}", msg))
+    } else {
+        Error::new(Category::Data, format_args!("`{}` {}", pointer, msg))
+    };
+    err.with_pointer(pointer)
+}
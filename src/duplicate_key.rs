@@ -0,0 +1,30 @@
+use crate::spanned;
+
+use std::ops::Range;
+
+
+
+/// An object key that was already used earlier in the same object, such as the second `"a"` in `{"a": 1, "a": 2}`.
+///
+/// See [DuplicateKeys::Collect](crate::DuplicateKeys::Collect) and [Deserializer::take_duplicate_keys](crate::Deserializer::take_duplicate_keys).
+#[derive(Clone, Debug)]
+pub struct DuplicateKey {
+    key:        spanned::String,
+    first:      Range<usize>,
+    pointer:    String,
+}
+
+impl DuplicateKey {
+    pub(crate) fn new(range: Range<usize>, key: String, first: Range<usize>, pointer: String) -> Self {
+        Self { key: spanned::String { start: range.start, end: range.end, value: key }, first, pointer }
+    }
+
+    /// The repeated key, spanning its quotes (if any.)
+    pub fn key(&self) -> &spanned::String { &self.key }
+
+    /// The byte range of the key's first use within the same object, spanning its quotes (if any.)
+    pub fn first(&self) -> Range<usize> { self.first.clone() }
+
+    /// [JSON pointer](https://tools.ietf.org/html/rfc6901) of the repeated key's value, such as `/servers/3/port`.
+    pub fn pointer(&self) -> &str { &self.pointer }
+}
@@ -0,0 +1,345 @@
+use serde::de;
+use serde_json::error as sje;
+
+use std::fmt::{self, Debug, Display, Formatter};
+use std::io;
+use std::ops::Range;
+
+pub use sje::Category;
+
+
+
+/// Create a [de::Error] for an explicit byte `range` of the input, such as the [range](crate::Spanned::range) of a value that failed validation.
+///
+/// If `E` is [Error] - as it is when deserializing through this crate - the error [range](Error::range), line, and column will refer to `range`.
+/// Other error types just get `msg`.  See also [Spanned::error](crate::Spanned::error).
+///
+/// The range travels inside the message passed to [de::Error::custom], so it's lost if anything in between reformats that message -
+/// an adapter that wraps or prefixes messages, or `format_args!` with its own padding.  The error then has just the message,
+/// located like any other error.
+pub fn error_at<E: de::Error>(range: Range<usize>, msg: impl Display) -> E {
+    E::custom(RangedMessage { range, msg })
+}
+
+/// Passes a range through [de::Error::custom], which only accepts [Display].
+///
+/// Formatted normally, this is just `msg`.  [Error]'s [de::Error::custom] formats messages with a `\0` fill character instead -
+/// which has no effect without a width - and this responds by prefixing `msg` with a [RANGE_MARKER]ed `start..end` for it to [parse](Self::parse).
+struct RangedMessage<T: Display> {
+    range:  Range<usize>,
+    msg:    T,
+}
+
+/// Specific enough that messages which merely contain `\0` aren't mistaken for a [RangedMessage].
+const RANGE_MARKER : &str = "\0json_spanned_value::error_at ";
+
+impl<T: Display> Display for RangedMessage<T> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        if fmt.fill() == '\0' { write!(fmt, "{}{}..{}\0", RANGE_MARKER, self.range.start, self.range.end)?; }
+        write!(fmt, "{}", self.msg)
+    }
+}
+
+impl RangedMessage<&str> {
+    fn parse(message: &str) -> Option<RangedMessage<&str>> {
+        let message = message.strip_prefix(RANGE_MARKER)?;
+        let end = message.find('\0')?;
+        let (range, msg) = (&message[.. end], &message[end + 1 ..]);
+        let dots = range.find("..")?;
+        let range = range[.. dots].parse().ok()? .. range[dots + 2 ..].parse().ok()?;
+        Some(RangedMessage { range, msg })
+    }
+}
+
+/// Alias for a [Result](std::result::Result) with the error type [Error].
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// An error that occurred while parsing or deserializing json.
+///
+/// Unlike [serde_json::Error], this knows the byte [range](Self::range) of the
+/// offending json, and the [pointer](Self::pointer) of the value being
+/// deserialized when the error occurred.  Converts to and from
+/// [serde_json::Error] when you need to interoperate with code expecting one.
+pub struct Error(Box<ErrorImpl>);
+
+struct ErrorImpl {
+    message:    String,
+    category:   Category,
+    offset:     Option<usize>,
+    range:      Option<Range<usize>>,
+    line:       usize,
+    column:     usize,
+    pointer:    String,
+    io:         Option<io::Error>,
+    /// Set by [de::Error::missing_field], for [crate::from_str_collect_errors] to fill in.
+    missing_field: Option<&'static str>,
+    suggestion: Option<&'static str>,
+    duplicate_of: Option<Range<usize>>,
+}
+
+impl Error {
+    pub(crate) fn new(category: Category, message: impl Display) -> Self {
+        Self(Box::new(ErrorImpl {
+            message: message.to_string(),
+            category,
+            offset: None,
+            range:  None,
+            line:   0,
+            column: 0,
+            pointer: String::new(),
+            io:     None,
+            missing_field: None,
+            suggestion: None,
+            duplicate_of: None,
+        }))
+    }
+
+    pub(crate) fn io(err: io::Error) -> Self {
+        let mut e = Self::new(Category::Io, &err);
+        e.0.io = Some(err);
+        e
+    }
+
+    /// An I/O error, prefixed with some context such as `unable to open example.json`
+    pub(crate) fn io_context(context: impl Display, err: io::Error) -> Self {
+        let mut e = Self::new(Category::Io, format_args!("{}: {}", context, err));
+        e.0.io = Some(err);
+        e
+    }
+
+    /// A repeated object `key`, first used at `first`
+    pub(crate) fn duplicate_key(key: &str, first: Range<usize>) -> Self {
+        let mut e = Self::new(Category::Data, format_args!("duplicate key `{}`", key));
+        e.0.duplicate_of = Some(first);
+        e
+    }
+
+    pub(crate) fn has_position(&self) -> bool { self.0.line != 0 }
+
+    pub(crate) fn set_position(&mut self, offset: usize, (line, column): (usize, usize), range: Range<usize>) {
+        self.0.offset   = Some(offset);
+        self.0.line     = line;
+        self.0.column   = column;
+        self.0.range    = Some(range);
+    }
+
+    /// Blame a range of json without knowing its line or column (e.g. when deserializing from a [spanned::Value](crate::spanned::Value))
+    pub(crate) fn set_range(&mut self, range: Range<usize>) {
+        self.0.offset   = Some(range.start);
+        self.0.range    = Some(range);
+    }
+
+    pub(crate) fn missing_field(&self) -> Option<&'static str> { self.0.missing_field }
+
+    /// Prepend an object key to [pointer](Self::pointer)
+    pub(crate) fn at_key(mut self, key: &str) -> Self {
+        let escaped = key.replace('~', "~0").replace('/', "~1");
+        self.0.pointer.insert_str(0, &escaped);
+        self.0.pointer.insert(0, '/');
+        self
+    }
+
+    /// Replace [pointer](Self::pointer)
+    pub(crate) fn with_pointer(mut self, pointer: &str) -> Self {
+        self.0.pointer = pointer.to_string();
+        self
+    }
+
+    /// Prepend an array index to [pointer](Self::pointer)
+    pub(crate) fn at_index(mut self, index: usize) -> Self {
+        self.0.pointer.insert_str(0, &format!("/{}", index));
+        self
+    }
+
+    /// The category of error: I/O, syntax, data, or unexpected EOF.
+    pub fn category(&self) -> Category { self.0.category }
+
+    /// True if this error was caused by a failure to read or write bytes on an I/O stream.
+    pub fn is_io(&self) -> bool { self.0.category == Category::Io }
+
+    /// True if this error was caused by input that was not syntactically valid json.
+    pub fn is_syntax(&self) -> bool { self.0.category == Category::Syntax }
+
+    /// True if this error was caused by input data that was semantically incorrect, such as a string where a number was expected.
+    pub fn is_data(&self) -> bool { self.0.category == Category::Data }
+
+    /// True if this error was caused by prematurely reaching the end of the input.
+    pub fn is_eof(&self) -> bool { self.0.category == Category::Eof }
+
+    /// **One**-based line at which the error was detected, or `0` if unknown.
+    pub fn line(&self) -> usize { self.0.line }
+
+    /// **One**-based byte column at which the error was detected, or `0` if unknown.
+    pub fn column(&self) -> usize { self.0.column }
+
+    /// **Zero**-based byte offset at which the error was detected, if known.
+    pub fn offset(&self) -> Option<usize> { self.0.offset }
+
+    /// **Zero**-based byte range of the offending json, if known.
+    ///
+    /// For syntax errors, this is the unexpected character (empty at EOF.)
+    /// For data errors, this is the value that couldn't be deserialized.
+    pub fn range(&self) -> Option<Range<usize>> { self.0.range.clone() }
+
+    /// [JSON pointer](https://tools.ietf.org/html/rfc6901) of the value being deserialized when the error occurred, such as `/servers/3/port`.
+    /// An empty string refers to the root value.
+    pub fn pointer(&self) -> &str { &self.0.pointer }
+
+    /// For unknown field or enum variant errors, the expected name most similar to the unknown one, if any are similar enough.
+    /// The [range](Self::range) of such errors is the unknown key or variant.
+    ///
+    /// ```
+    /// # use json_spanned_value as jsv;
+    /// # use serde::Deserialize;
+    /// #[derive(Debug, Deserialize)] #[serde(deny_unknown_fields)]
+    /// struct Config { timeout: u32, retries: u32 }
+    ///
+    /// let json = "{\"timeuot\": 5}";
+    /// let err = jsv::from_str::<Config>(json).unwrap_err();
+    /// assert_eq!(&json[err.range().unwrap()], "\"timeuot\"");
+    /// assert_eq!(err.suggestion(), Some("timeout"));
+    /// ```
+    pub fn suggestion(&self) -> Option<&str> { self.0.suggestion }
+
+    /// For [duplicate key](crate::DuplicateKeys::Error) errors, the byte range of the key's first use.
+    /// The [range](Self::range) of such errors is the repeated key.
+    pub fn duplicate_of(&self) -> Option<Range<usize>> { self.0.duplicate_of.clone() }
+}
+
+impl Debug for Error {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "Error({:?}, line: {}, column: {}, range: {:?}, pointer: {:?})", self.0.message, self.0.line, self.0.column, self.0.range, self.0.pointer)
+    }
+}
+
+impl Display for Error {
+    /// `{:#}` prefixes the message with the [pointer](Self::pointer), if any, such as `/servers/3/port: invalid type: ...`
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        if fmt.alternate() && !self.0.pointer.is_empty() { write!(fmt, "{}: ", self.0.pointer)?; }
+        if self.0.line == 0 {
+            write!(fmt, "{}", self.0.message)
+        } else {
+            write!(fmt, "{} at line {} column {}", self.0.message, self.0.line, self.0.column)
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self.0.io.as_ref() {
+            Some(io) => Some(io),
+            None => None,
+        }
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        let message = format!("{:\0<}", msg); // see RangedMessage
+        match RangedMessage::parse(&message) {
+            Some(ranged)    => { let mut err = Self::new(Category::Data, ranged.msg); err.set_range(ranged.range); err },
+            None            => Self::new(Category::Data, message),
+        }
+    }
+
+    fn unknown_field(field: &str, expected: &'static [&'static str]) -> Self {
+        let mut err = Self::new(Category::Data, format_args!("unknown field `{}`, {}", field, OneOf(expected, "fields")));
+        err.0.suggestion = closest(field, expected);
+        err
+    }
+
+    fn unknown_variant(variant: &str, expected: &'static [&'static str]) -> Self {
+        let mut err = Self::new(Category::Data, format_args!("unknown variant `{}`, {}", variant, OneOf(expected, "variants")));
+        err.0.suggestion = closest(variant, expected);
+        err
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        let mut err = Self::new(Category::Data, format_args!("missing field `{}`", field));
+        err.0.missing_field = Some(field);
+        err
+    }
+}
+
+/// Matches the wording of serde's default [de::Error::unknown_field] and [de::Error::unknown_variant] messages.
+struct OneOf(&'static [&'static str], &'static str);
+
+impl Display for OneOf {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match self.0 {
+            []          => write!(fmt, "there are no {}", self.1),
+            [a]         => write!(fmt, "expected `{}`", a),
+            [a, b]      => write!(fmt, "expected `{}` or `{}`", a, b),
+            [a, rest @ ..] => {
+                write!(fmt, "expected one of `{}`", a)?;
+                rest.iter().try_for_each(|name| write!(fmt, ", `{}`", name))
+            },
+        }
+    }
+}
+
+/// The name in `expected` closest to `name`: an exact match ignoring case, or else the fewest edits (up to a third of `name`'s length.)
+fn closest(name: &str, expected: &'static [&'static str]) -> Option<&'static str> {
+    if let Some(exact) = expected.iter().find(|e| e.eq_ignore_ascii_case(name)) { return Some(exact) }
+    let max = (name.chars().count() / 3).max(1);
+    expected.iter().map(|e| (edit_distance(name, e), *e)).filter(|(d, _)| *d <= max).min_by_key(|(d, _)| *d).map(|(_, e)| e)
+}
+
+/// [Levenshtein distance](https://en.wikipedia.org/wiki/Levenshtein_distance) between `a` and `b`, with transpositions counting as a single edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    // Rows for b[..j-2], b[..j-1], and b[..j]
+    let mut prev2 = vec![0; a.len() + 1];
+    let mut prev  = (0 ..= a.len()).collect::<Vec<_>>();
+    let mut row   = vec![0; a.len() + 1];
+    for j in 1 ..= b.len() {
+        row[0] = j;
+        for i in 1 ..= a.len() {
+            let substitution = prev[i-1] + usize::from(a[i-1] != b[j-1]);
+            row[i] = substitution.min(prev[i] + 1).min(row[i-1] + 1);
+            if i > 1 && j > 1 && a[i-1] == b[j-2] && a[i-2] == b[j-1] { row[i] = row[i].min(prev2[i-2] + 1); }
+        }
+        std::mem::swap(&mut prev2, &mut prev);
+        std::mem::swap(&mut prev, &mut row);
+    }
+    prev[a.len()]
+}
+
+impl From<sje::Error> for Error {
+    /// Line and column are preserved, but [serde_json::Error] has no byte range or pointer to convert.
+    fn from(err: sje::Error) -> Self {
+        let (category, line, column) = (err.classify(), err.line(), err.column());
+        let mut message = err.to_string();
+        let suffix = format!(" at line {} column {}", line, column);
+        if line != 0 && message.ends_with(&suffix) { message.truncate(message.len() - suffix.len()); }
+        let mut e = Self::new(category, message);
+        e.0.line    = line;
+        e.0.column  = column;
+        if category == Category::Io { e.0.io = Some(io::Error::from(err)); }
+        e
+    }
+}
+
+impl From<Error> for sje::Error {
+    /// [serde_json::Error] has no public constructors for syntax or EOF errors, so those become data errors.
+    /// I/O errors, the message, line, and column are preserved.
+    fn from(err: Error) -> Self {
+        let mut err = err.0;
+        match err.io.take() {
+            Some(io) if err.line == 0   => sje::Error::io(io),
+            _other                      => de::Error::custom(Error(err)),
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        let mut err = err.0;
+        match (err.io.take(), err.category) {
+            (Some(io), _)       => io,
+            (None, Category::Eof) => io::Error::new(io::ErrorKind::UnexpectedEof, Error(err)),
+            (None, _)           => io::Error::new(io::ErrorKind::InvalidData, Error(err)),
+        }
+    }
+}
@@ -1,21 +1,63 @@
-/// Utility methods extending [serde_json::Error]
+use crate::LineIndex;
+
+/// Utility methods extending [serde_json::Error] and [crate::Error]
 pub trait ErrorExt {
     /// **Zero**-based byte offset at which the error was detected.
     /// Returns `None` if this reaches or exceeds `text.len()`.
     fn offset_within(&self, text: &str) -> Option<usize>;
+
+    /// **One**-based (line, column) at which the error was detected, if known.
+    /// The default implementation returns `None`.
+    fn line_column(&self) -> Option<(usize, usize)> { None }
+
+    /// **Zero**-based byte offset at which the error was detected, using a prebuilt [LineIndex] instead of rescanning the text.
+    /// Returns `None` if this reaches or exceeds the length of the indexed text.
+    /// The default implementation converts [line_column](Self::line_column).
+    fn offset_within_index(&self, index: &LineIndex) -> Option<usize> {
+        let (line, column) = self.line_column()?;
+        index_offset_of(line, column, index)
+    }
 }
 
 impl ErrorExt for serde_json::Error {
     fn offset_within(&self, text: &str) -> Option<usize> {
-        let mut remaining = text;
-        for _ in 1..self.line() {
-            match remaining.find('\n') {
-                None => return None,
-                Some(n) => remaining = &remaining[(n+1)..],
-            };
+        offset_of(self.line(), self.column(), text)
+    }
+
+    fn line_column(&self) -> Option<(usize, usize)> { Some((self.line(), self.column())) }
+}
+
+impl ErrorExt for crate::Error {
+    fn offset_within(&self, text: &str) -> Option<usize> {
+        match self.offset() {
+            Some(n) => if n < text.len() { Some(n) } else { None },
+            None    => offset_of(self.line(), self.column(), text), // e.g. converted from a serde_json::Error
         }
+    }
+
+    fn line_column(&self) -> Option<(usize, usize)> { Some((self.line(), self.column())) }
 
-        let n = (text.len() - remaining.len()).checked_add(self.column().saturating_sub(1))?;
-        return if n < text.len() { Some(n) } else { None };
+    fn offset_within_index(&self, index: &LineIndex) -> Option<usize> {
+        match self.offset() {
+            Some(n) => if n < index.text_len() { Some(n) } else { None },
+            None    => index_offset_of(self.line(), self.column(), index),
+        }
     }
 }
+
+fn index_offset_of(line: usize, column: usize, index: &LineIndex) -> Option<usize> {
+    index.offset(line, column).filter(|n| *n < index.text_len())
+}
+
+fn offset_of(line: usize, column: usize, text: &str) -> Option<usize> {
+    let mut remaining = text;
+    for _ in 1..line {
+        match remaining.find('\n') {
+            None => return None,
+            Some(n) => remaining = &remaining[(n+1)..],
+        };
+    }
+
+    let n = (text.len() - remaining.len()).checked_add(column.saturating_sub(1))?;
+    if n < text.len() { Some(n) } else { None }
+}
@@ -0,0 +1,221 @@
+use crate::{spanned, Comment, Comments, DuplicateKeys, Result, Settings, Value};
+
+
+
+/// Options for [format()]
+#[derive(Clone, Copy, Debug)]
+pub struct FormatOptions {
+    /// Spaces per level of indentation - or, if indenting with [tabs](Self::tabs), how many columns a tab counts as for [line_width](Self::line_width).<br>
+    /// **default: 4**
+    pub indent: usize,
+
+    /// Indent with a tab per level instead of spaces.<br>
+    /// **default: false**
+    pub tabs: bool,
+
+    /// How many columns arrays may fill before they're wrapped, per [array_wrap](Self::array_wrap).<br>
+    /// **default: 100**
+    pub line_width: usize,
+
+    /// How to lay out arrays.<br>
+    /// **default: [ArrayWrap::Fit]**
+    pub array_wrap: ArrayWrap,
+
+    /// Where to write trailing commas.  Note that parsing them again requires [Settings::allow_trailing_comma].<br>
+    /// **default: [TrailingCommas::Never]**
+    pub trailing_commas: TrailingCommas,
+
+    #[doc(hidden)] pub _non_exhaustive: ()
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self { indent: 4, tabs: false, line_width: 100, array_wrap: ArrayWrap::default(), trailing_commas: TrailingCommas::default(), _non_exhaustive: () }
+    }
+}
+
+/// How [format()] lays out arrays.  Objects are always written a member per line, and arrays are too if they contain comments, or any non-empty arrays or objects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ArrayWrap {
+    /// An element per line.
+    Always,
+
+    /// On a single line, if it fits within [FormatOptions::line_width] - otherwise an element per line.
+    Fit,
+
+    /// On a single line, if it fits - otherwise as many elements per line as fit.
+    Fill,
+}
+
+impl Default for ArrayWrap {
+    fn default() -> Self { ArrayWrap::Fit }
+}
+
+/// Where [format()] writes trailing commas.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TrailingCommas {
+    /// Never: `[\n    1,\n    2\n]`
+    Never,
+
+    /// After the last element or member of arrays and objects spanning multiple lines: `[\n    1,\n    2,\n]`
+    Multiline,
+}
+
+impl Default for TrailingCommas {
+    fn default() -> Self { TrailingCommas::Never }
+}
+
+/// Reformat `json`, keeping every comment with what it's commenting on (see [Comments].)
+///
+/// Keys, strings, and numbers are written exactly as they appear in `json`, so JSON5 identifiers, quotes, and numbers survive.
+/// Formatting the result again won't change it.  Duplicate keys are always an error, whatever [Settings::duplicate_keys] says, as only one of them could be written.
+///
+/// ```
+/// # use json_spanned_value as jsv;
+/// let json = "{\"name\": \"example\", // TODO: rename\n\"tags\": [\"a\",\"b\"], \"nested\": {\"empty\": {}}}";
+/// let settings = jsv::Settings { allow_comments: true, ..Default::default() };
+/// let options = jsv::FormatOptions { indent: 2, ..Default::default() };
+/// assert_eq!(jsv::format(json, &settings, &options).unwrap(), "\
+/// {
+///   \"name\": \"example\", // TODO: rename
+///   \"tags\": [\"a\", \"b\"],
+///   \"nested\": {
+///     \"empty\": {}
+///   }
+/// }
+/// ");
+/// ```
+pub fn format(json: &str, settings: &Settings, options: &FormatOptions) -> Result<String> {
+    let settings = Settings { duplicate_keys: Some(DuplicateKeys::Error), ..*settings };
+    let (value, comments) = crate::from_str_with_comments(json, &settings)?;
+    let mut f = Formatter { json, comments: &comments, options, out: String::new() };
+    for comment in comments.leading("") { f.comment_line(comment, 0); }
+    f.value(&value, 0, &mut String::new());
+    for comment in comments.trailing("") {
+        f.out.push('\n');
+        f.comment(comment);
+    }
+    f.out.push('\n');
+    Ok(f.out)
+}
+
+struct Formatter<'a> {
+    json:       &'a str,
+    comments:   &'a Comments,
+    options:    &'a FormatOptions,
+    out:        String,
+}
+
+impl Formatter<'_> {
+    fn indent(&mut self, depth: usize) {
+        for _ in 0 .. depth {
+            if self.options.tabs { self.out.push('\t') } else { self.out.push_str(&" ".repeat(self.options.indent)) }
+        }
+    }
+
+    /// The column the next character would be written at
+    fn column(&self) -> usize {
+        let line = &self.out[self.out.rfind('\n').map_or(0, |n| n + 1) ..];
+        line.chars().map(|ch| if ch == '\t' { self.options.indent } else { 1 }).sum()
+    }
+
+    fn comment(&mut self, comment: &Comment) {
+        self.out.push_str(&self.json[comment.range()]);
+    }
+
+    fn comment_line(&mut self, comment: &Comment, depth: usize) {
+        self.indent(depth);
+        self.comment(comment);
+        self.out.push('\n');
+    }
+
+    fn value(&mut self, node: &spanned::Value, depth: usize, pointer: &mut String) {
+        let (open, close, children) = match node.get_ref() {
+            Value::Array(a)     => ('[', ']', a.iter().enumerate().map(|(i, v)| (None, i.to_string(), v)).collect::<Vec<_>>()),
+            Value::Object(o)    => ('{', '}', o.iter().map(|(k, v)| (Some(k), k.replace('~', "~0").replace('/', "~1"), v)).collect()),
+            _scalar             => return self.out.push_str(&self.json[node.range()]),
+        };
+        let mut children = children;
+        children.sort_by_key(|(key, _, value)| key.map_or(value.start(), |k| k.start()));
+
+        let parent = pointer.len();
+        let child_pointer = |pointer: &mut String, segment: &str| { pointer.truncate(parent); pointer.push('/'); pointer.push_str(segment); };
+        let dangling = self.comments.dangling(pointer);
+        if children.is_empty() && dangling.is_empty() {
+            self.out.push(open);
+            self.out.push(close);
+            return;
+        }
+
+        // Arrays of scalars (or empty arrays and objects) without comments may share lines
+        let simple = open == '[' && dangling.is_empty() && children.iter().all(|(_, segment, value)| {
+            child_pointer(pointer, segment);
+            let empty = match value.get_ref() { Value::Array(a) => a.is_empty(), Value::Object(o) => o.is_empty(), _scalar => true };
+            empty && self.comments.leading(pointer).is_empty() && self.comments.trailing(pointer).is_empty() && self.comments.dangling(pointer).is_empty()
+        });
+        pointer.truncate(parent);
+        if simple && self.options.array_wrap != ArrayWrap::Always {
+            let elements = children.iter().map(|(_, _, value)| self.inline(value)).collect::<Vec<_>>();
+            let width = elements.iter().map(|e| e.chars().count() + 2).sum::<usize>();
+            if self.column() + width < self.options.line_width {
+                self.out.push(open);
+                self.out.push_str(&elements.join(", "));
+                self.out.push(close);
+                return;
+            }
+            if self.options.array_wrap == ArrayWrap::Fill {
+                self.out.push(open);
+                let last = elements.len() - 1;
+                for (i, element) in elements.iter().enumerate() {
+                    let comma = i != last || self.options.trailing_commas == TrailingCommas::Multiline;
+                    let width = element.chars().count() + usize::from(comma);
+                    if i == 0 || self.column() + 1 + width > self.options.line_width {
+                        self.out.push('\n');
+                        self.indent(depth + 1);
+                    } else {
+                        self.out.push(' ');
+                    }
+                    self.out.push_str(element);
+                    if comma { self.out.push(','); }
+                }
+                self.out.push('\n');
+                self.indent(depth);
+                self.out.push(close);
+                return;
+            }
+        }
+
+        self.out.push(open);
+        self.out.push('\n');
+        let last = children.len().wrapping_sub(1);
+        for (i, (key, segment, value)) in children.iter().enumerate() {
+            child_pointer(pointer, segment);
+            for comment in self.comments.leading(pointer) { self.comment_line(comment, depth + 1); }
+            self.indent(depth + 1);
+            if let Some(key) = key {
+                self.out.push_str(&self.json[key.range()]);
+                self.out.push_str(": ");
+            }
+            self.value(value, depth + 1, pointer);
+            if i != last || self.options.trailing_commas == TrailingCommas::Multiline { self.out.push(','); }
+            for comment in self.comments.trailing(pointer) {
+                self.out.push(' ');
+                self.comment(comment);
+            }
+            self.out.push('\n');
+        }
+        pointer.truncate(parent);
+        for comment in dangling { self.comment_line(comment, depth + 1); }
+        self.indent(depth);
+        self.out.push(close);
+    }
+
+    /// A scalar, or empty array or object, on a single line
+    fn inline(&self, node: &spanned::Value) -> String {
+        match node.get_ref() {
+            Value::Array(_)     => "[]".to_string(),
+            Value::Object(_)    => "{}".to_string(),
+            _scalar             => self.json[node.range()].to_string(),
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use crate::spanned;
+
+use std::ops::Range;
+
+
+
+/// An object key whose value was skipped during deserialization, such as an unknown (or misspelled!) field of a struct without `#[serde(deny_unknown_fields)]`.
+///
+/// See [Deserializer::track_ignored_keys](crate::Deserializer::track_ignored_keys) and [from_str_report_ignored](crate::from_str_report_ignored).
+#[derive(Clone, Debug)]
+pub struct IgnoredKey {
+    key:        spanned::String,
+    pointer:    String,
+}
+
+impl IgnoredKey {
+    pub(crate) fn new(range: Range<usize>, key: String, pointer: String) -> Self {
+        Self { key: spanned::String { start: range.start, end: range.end, value: key }, pointer }
+    }
+
+    /// The ignored key, spanning its quotes (if any.)
+    pub fn key(&self) -> &spanned::String { &self.key }
+
+    /// [JSON pointer](https://tools.ietf.org/html/rfc6901) of the ignored key's value, such as `/servers/3/prot`.
+    pub fn pointer(&self) -> &str { &self.pointer }
+}
@@ -17,43 +17,268 @@
 #![forbid(missing_docs)]
 #![forbid(unsafe_code)]
 
+mod comments;               pub use comments::{Comment, Comments};
+mod deserializer;           pub use deserializer::Deserializer;
+mod document;               pub use document::Document;
+mod duplicate_key;          pub use duplicate_key::DuplicateKey;
+mod error;                  pub use error::*;
 mod error_ext;              pub use error_ext::*;
+mod formatter;              pub use formatter::{format, FormatOptions, ArrayWrap, TrailingCommas};
+mod ignored_key;            pub use ignored_key::IgnoredKey;
+mod line_index;             pub use line_index::{LineIndex, ColumnUnit};
 mod map;                    pub use map::Map;
-mod reader;                 pub(crate) use reader::*;
+mod repair;
+mod reader;                 pub use reader::{Read, SliceRead, IoRead}; pub(crate) use reader::*;
 mod settings;               pub use settings::*;
-mod shared;                 pub(crate) use shared::*;
 pub mod spanned;            pub use spanned::Spanned;
 mod stream_deserializer;    pub use stream_deserializer::StreamDeserializer;
+mod strip;                  pub use strip::strip_jsonc;
+mod tokenizer;              pub use tokenizer::{Token, TokenKind, Tokenizer};
 mod value;                  pub use value::Value;
+mod value_deserializer;
 
 #[cfg(test)] mod tests;
 
 
 
-use serde_json::error as sje;
-use serde::de;
-use std::sync::Arc;
+use serde::{de, ser};
+use std::fs::File;
+use std::io;
+use std::path::Path;
 
 
 /// Read json from a slice of in-memory bytes, with explicit [Settings]
-pub fn from_slice_with_settings<T: de::DeserializeOwned>(buf: &[u8], settings: &Settings) -> sje::Result<T> {
-    let shared = Arc::new(Shared::new(settings));
-    let _shared_stack = SharedStack::push(shared.clone());
-    // NOTE:  Our use of from_reader forces us to use DeserializeOwned
-    serde_json::from_reader(Reader::new(buf, shared))
+pub fn from_slice_with_settings<'de, T: de::Deserialize<'de>>(buf: &'de [u8], settings: &Settings) -> Result<T> {
+    Deserializer::from_slice(buf, settings).deserialize_root()
 }
 
 /// Read json from a slice of in-memory bytes, with default [Settings]
-pub fn from_slice<T: de::DeserializeOwned>(buf: &[u8]) -> sje::Result<T> {
+pub fn from_slice<'de, T: de::Deserialize<'de>>(buf: &'de [u8]) -> Result<T> {
     from_slice_with_settings(buf, &Settings::default())
 }
 
 /// Read json from an in-memory string, with explicit [Settings]
-pub fn from_str_with_settings<T: de::DeserializeOwned>(buf: &str, settings: &Settings) -> sje::Result<T> {
+pub fn from_str_with_settings<'de, T: de::Deserialize<'de>>(buf: &'de str, settings: &Settings) -> Result<T> {
     from_slice_with_settings(buf.as_bytes(), settings)
 }
 
 /// Read json from an in-memory string, with default [Settings]
-pub fn from_str<T: de::DeserializeOwned>(buf: &str) -> sje::Result<T> {
+pub fn from_str<'de, T: de::Deserialize<'de>>(buf: &'de str) -> Result<T> {
     from_slice(buf.as_bytes())
 }
+
+/// Read a [spanned::Value] from an in-memory string, along with its [Comments], with explicit [Settings]
+///
+/// Comments are only allowed if [Settings::allow_comments] (or [allow_json5](Settings::allow_json5)) is set.
+pub fn from_str_with_comments(buf: &str, settings: &Settings) -> Result<(spanned::Value, Comments)> {
+    let mut de = Deserializer::from_str(buf, settings);
+    de.track_comments();
+    let value = de.deserialize_root()?;
+    let comments = Comments::new(buf, &value, de.take_comments());
+    Ok((value, comments))
+}
+
+/// Read a [spanned::Value] from an in-memory string that may not be valid json, along with every syntax error, with explicit [Settings]
+///
+/// Instead of stopping at the first syntax error, parsing recovers and keeps going - for editors and linters, which mostly see broken json.
+/// Unexpected tokens are skipped, missing or malformed values are replaced with a `null` placeholder spanning nothing (e.g. `3 .. 3`),
+/// members missing their key are dropped, and unclosed arrays and objects end where their parent (or the input) does.
+/// Each error has a [range](Error::range), line, column, and [pointer](Error::pointer), in the order found.
+/// The errors are empty exactly when [from_str_with_settings] would succeed.
+///
+/// ```
+/// # use json_spanned_value as jsv;
+/// let json = "{\"a\": [1, 2 3], \"b\": , \"c\": tru}";
+/// let (value, errors) = jsv::from_str_partial(json, &jsv::Settings::default());
+/// let errors = errors.iter().map(|e| format!("{:#}", e)).collect::<Vec<_>>();
+/// assert_eq!(errors, [
+///     "/a: expected `,` or `]` at line 1 column 13",
+///     "/b: expected value at line 1 column 22",
+///     "/c: expected ident at line 1 column 32",
+/// ]);
+/// assert_eq!(value.pointer("/a/2").unwrap().as_number().unwrap().as_u64(), Some(3));
+/// let b = value.pointer("/b").unwrap();
+/// assert!(b.is_null() && b.range().is_empty());
+/// ```
+pub fn from_str_partial(buf: &str, settings: &Settings) -> (spanned::Value, Vec<Error>) {
+    Deserializer::from_str(buf, settings).parse_partial()
+}
+
+/// Read json from a slice of in-memory bytes, along with every object key that was ignored, with explicit [Settings]
+///
+/// See [Deserializer::track_ignored_keys] for what counts as ignored.
+pub fn from_slice_report_ignored_with_settings<'de, T: de::Deserialize<'de>>(buf: &'de [u8], settings: &Settings) -> Result<(T, Vec<IgnoredKey>)> {
+    let mut de = Deserializer::from_slice(buf, settings);
+    de.track_ignored_keys();
+    let value = de.deserialize_root()?;
+    Ok((value, de.take_ignored_keys()))
+}
+
+/// Read json from a slice of in-memory bytes, along with every object key that was ignored, with default [Settings]
+///
+/// See [Deserializer::track_ignored_keys] for what counts as ignored.
+pub fn from_slice_report_ignored<'de, T: de::Deserialize<'de>>(buf: &'de [u8]) -> Result<(T, Vec<IgnoredKey>)> {
+    from_slice_report_ignored_with_settings(buf, &Settings::default())
+}
+
+/// Read json from an in-memory string, along with every object key that was ignored, with explicit [Settings]
+///
+/// See [Deserializer::track_ignored_keys] for what counts as ignored.
+pub fn from_str_report_ignored_with_settings<'de, T: de::Deserialize<'de>>(buf: &'de str, settings: &Settings) -> Result<(T, Vec<IgnoredKey>)> {
+    from_slice_report_ignored_with_settings(buf.as_bytes(), settings)
+}
+
+/// Read json from an in-memory string, along with every object key that was ignored, with default [Settings]
+///
+/// ```
+/// # use json_spanned_value as jsv;
+/// # use serde::Deserialize;
+/// #[derive(Deserialize)] struct Config { #[serde(default)] verbose: bool }
+///
+/// let json = "{\"verbsoe\": true}";
+/// let (config, ignored) = jsv::from_str_report_ignored::<Config>(json).unwrap();
+/// assert!(!config.verbose);
+/// for ignored in ignored.iter() {
+///     let (line, column) = jsv::LineIndex::new(json).line_col(ignored.key().start()).unwrap();
+///     println!("warning: unused key {} at line {} column {}", ignored.pointer(), line, column);
+/// }
+/// assert_eq!(ignored[0].pointer(), "/verbsoe");
+/// ```
+pub fn from_str_report_ignored<'de, T: de::Deserialize<'de>>(buf: &'de str) -> Result<(T, Vec<IgnoredKey>)> {
+    from_slice_report_ignored(buf.as_bytes())
+}
+
+/// Read json from an in-memory string, reporting as many errors as possible instead of stopping at the first, with explicit [Settings]
+///
+/// Deserialization keeps going past type mismatches, missing fields, unknown fields (for `#[serde(deny_unknown_fields)]`),
+/// and validation errors from custom [Deserialize](de::Deserialize) impls, by repairing the document and trying again:
+/// unknown fields are skipped, and values that fail (or are missing) are replaced with a placeholder - the simplest value of
+/// the expected type, such as `0`, `""`, `None`, or an empty [Vec].  If the placeholder is rejected too, the value is removed
+/// from its parent (an [Option], collection, or `#[serde(default)]` field), and so on up the document.
+///
+/// Returns the partially built value (with placeholders), or `None` if that was impossible (including for syntax errors, which
+/// stop parsing.)  Each error has a [range](Error::range), line, column, and [pointer](Error::pointer), in the order found.
+/// Errors caused by earlier repairs aren't reported.
+///
+/// serde can't resume a failed deserialization, so the document is parsed once but deserialized again after every repair:
+/// expect this to take time proportional to the number of errors times the size of the document.
+///
+/// ```
+/// # use json_spanned_value as jsv;
+/// # use serde::Deserialize;
+/// #[derive(Debug, Deserialize)] #[serde(deny_unknown_fields)]
+/// struct Server { name: String, port: u16 }
+///
+/// let json = r#"[
+///     {"name": "a", "port": "80"},
+///     {"name": "b", "prot": 8080},
+///     {"name": "c", "port": 443}
+/// ]"#;
+/// let (servers, errors) = jsv::from_str_collect_errors::<Vec<Server>>(json);
+/// let errors = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+/// assert_eq!(errors, [
+///     "invalid type: string \"80\", expected u16 at line 2 column 27",
+///     "missing field `port` at line 3 column 5",
+///     "unknown field `prot`, expected `name` or `port` at line 3 column 19",
+/// ]);
+/// let servers = servers.unwrap();
+/// assert_eq!(servers[0].port, 0); // placeholder
+/// assert_eq!(servers[2].port, 443);
+/// ```
+pub fn from_str_collect_errors_with_settings<T: de::DeserializeOwned>(buf: &str, settings: &Settings) -> (Option<T>, Vec<Error>) {
+    let value : spanned::Value = match from_str_with_settings(buf, settings) {
+        Ok(value)   => value,
+        Err(err)    => return (None, vec![err]),
+    };
+    let (value, mut errors) = repair::deserialize_collecting_errors(&value);
+    errors.sort_by_key(|err| err.offset());
+    let index = LineIndex::new(buf);
+    for err in errors.iter_mut() {
+        if let Some(range) = err.range().filter(|_| !err.has_position()) {
+            let position = index.line_col(range.start).unwrap_or((0, 0));
+            err.set_position(range.start, position, range);
+        }
+    }
+    (value, errors)
+}
+
+/// Read json from an in-memory string, reporting as many errors as possible instead of stopping at the first, with default [Settings]
+///
+/// See [from_str_collect_errors_with_settings] for details.
+pub fn from_str_collect_errors<T: de::DeserializeOwned>(buf: &str) -> (Option<T>, Vec<Error>) {
+    from_str_collect_errors_with_settings(buf, &Settings::default())
+}
+
+/// Read json from a slice of in-memory bytes into a stateful [DeserializeSeed](de::DeserializeSeed), with explicit [Settings]
+pub fn from_slice_seed_with_settings<'de, S: de::DeserializeSeed<'de>>(buf: &'de [u8], settings: &Settings, seed: S) -> Result<S::Value> {
+    Deserializer::from_slice(buf, settings).deserialize_root_seed(seed)
+}
+
+/// Read json from a slice of in-memory bytes into a stateful [DeserializeSeed](de::DeserializeSeed), with default [Settings]
+pub fn from_slice_seed<'de, S: de::DeserializeSeed<'de>>(buf: &'de [u8], seed: S) -> Result<S::Value> {
+    from_slice_seed_with_settings(buf, &Settings::default(), seed)
+}
+
+/// Read json from an in-memory string into a stateful [DeserializeSeed](de::DeserializeSeed), with explicit [Settings]
+pub fn from_str_seed_with_settings<'de, S: de::DeserializeSeed<'de>>(buf: &'de str, settings: &Settings, seed: S) -> Result<S::Value> {
+    from_slice_seed_with_settings(buf.as_bytes(), settings, seed)
+}
+
+/// Read json from an in-memory string into a stateful [DeserializeSeed](de::DeserializeSeed), with default [Settings]
+pub fn from_str_seed<'de, S: de::DeserializeSeed<'de>>(buf: &'de str, seed: S) -> Result<S::Value> {
+    from_slice_seed(buf.as_bytes(), seed)
+}
+
+/// Read json from an [io::Read] such as a [File] or [io::Stdin], with explicit [Settings]
+///
+/// Input is buffered internally - wrapping `reader` in a [io::BufReader] is unnecessary.
+/// Only a small window of it is kept in memory, except for the structs that [DuplicateKeys::KeepLast]
+/// and [Collect](crate::DuplicateKeys::Collect) scan ahead of time, which are buffered whole.
+pub fn from_reader_with_settings<R: io::Read, T: de::DeserializeOwned>(reader: R, settings: &Settings) -> Result<T> {
+    Deserializer::from_reader(reader, settings).deserialize_root()
+}
+
+/// Read json from an [io::Read] such as a [File] or [io::Stdin], with default [Settings]
+///
+/// Input is buffered internally - wrapping `reader` in a [io::BufReader] is unnecessary.
+pub fn from_reader<R: io::Read, T: de::DeserializeOwned>(reader: R) -> Result<T> {
+    from_reader_with_settings(reader, &Settings::default())
+}
+
+/// Read json from a file, with explicit [Settings]
+pub fn from_path_with_settings<P: AsRef<Path>, T: de::DeserializeOwned>(path: P, settings: &Settings) -> Result<T> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|err| Error::io_context(format_args!("unable to open {}", path.display()), err))?;
+    from_reader_with_settings(file, settings)
+}
+
+/// Read json from a file, with default [Settings]
+pub fn from_path<P: AsRef<Path>, T: de::DeserializeOwned>(path: P) -> Result<T> {
+    from_path_with_settings(path, &Settings::default())
+}
+
+/// Deserialize a `T` from a previously parsed [spanned::Value], such as a subtree picked out after inspecting a discriminator.
+///
+/// [Spanned] values within `T` get their spans from the original source, and errors report the [range](Error::range) and
+/// [pointer](Error::pointer) of the offending value - but no line or column, which a [LineIndex] can recover from the range.
+/// To borrow strings from `value` instead, use `T::deserialize(&value)`.
+pub fn from_value<T: de::DeserializeOwned>(value: spanned::Value) -> Result<T> {
+    T::deserialize(&value)
+}
+
+/// Serialize `value` as a compact json string, such as a [spanned::Value] or [Value] (spans are discarded.)
+pub fn to_string<T: ?Sized + ser::Serialize>(value: &T) -> Result<String> {
+    Ok(serde_json::to_string(value)?)
+}
+
+/// Serialize `value` as a pretty-printed json string, such as a [spanned::Value] or [Value] (spans are discarded.)
+pub fn to_string_pretty<T: ?Sized + ser::Serialize>(value: &T) -> Result<String> {
+    Ok(serde_json::to_string_pretty(value)?)
+}
+
+/// Serialize `value` as compact json into an [io::Write] such as a [File] or [io::Stdout].
+///
+/// Output is not buffered - consider wrapping `writer` in a [io::BufWriter].
+pub fn to_writer<W: io::Write, T: ?Sized + ser::Serialize>(writer: W, value: &T) -> Result<()> {
+    Ok(serde_json::to_writer(writer, value)?)
+}
@@ -0,0 +1,175 @@
+use std::ops::Range;
+
+
+
+/// Converts **zero**-based byte offsets (such as [Spanned::range](crate::Spanned::range)) to and from **one**-based line:column pairs, in `O(log lines)`.
+///
+/// Build once per source text and reuse it - construction scans the whole text.
+/// Lines are separated by `\n`, so `\r\n` line endings count as a single line break (matching the line numbers in [crate::Error] and [serde_json::Error].)
+/// Columns count bytes by default, also matching [crate::Error] and [serde_json::Error].
+/// Use [line_col_in](Self::line_col_in) and [offset_in](Self::offset_in) for UTF-16 (e.g. [LSP](https://microsoft.github.io/language-server-protocol/)) or [char] columns.
+///
+/// ```
+/// # use json_spanned_value::LineIndex;
+/// let index = LineIndex::new("{\r\n  \"a\": 1\r\n}");
+/// assert_eq!(index.line_col(5),   Some((2, 3)));
+/// assert_eq!(index.offset(2, 3),  Some(5));
+/// assert_eq!(index.line_range(2), Some(3 .. 11)); // excludes "\r\n"
+/// ```
+#[derive(Clone, Debug)]
+pub struct LineIndex {
+    /// Byte offset of the start of each line.  Always starts with `0`.
+    line_starts:    Vec<usize>,
+    /// Byte offset of the end of each line, excluding `\n` or `\r\n`.
+    line_ends:      Vec<usize>,
+    /// Byte offset and UTF-8 length of every non-ASCII [char], for converting columns to other units.
+    wide_chars:     Vec<(usize, usize)>,
+    len:            usize,
+}
+
+/// What [LineIndex] columns count.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ColumnUnit {
+    /// UTF-8 bytes, like [crate::Error::column] and [serde_json::Error::column].
+    Byte,
+    /// UTF-16 code units, like the [LSP](https://microsoft.github.io/language-server-protocol/)'s default `Position::character` encoding.
+    Utf16,
+    /// Unicode scalar values, like Rust [char]s.
+    Char,
+}
+
+impl ColumnUnit {
+    fn width(self, utf8_len: usize) -> usize {
+        match self {
+            ColumnUnit::Byte    => utf8_len,
+            ColumnUnit::Utf16   => if utf8_len == 4 { 2 } else { 1 },
+            ColumnUnit::Char    => 1,
+        }
+    }
+}
+
+impl LineIndex {
+    /// Index the line breaks of `text`.
+    pub fn new(text: &str) -> Self {
+        let bytes = text.as_bytes();
+        let mut line_starts = vec![0];
+        let mut line_ends   = Vec::new();
+        for (i, _) in bytes.iter().enumerate().filter(|(_, b)| **b == b'\n') {
+            line_starts.push(i + 1);
+            line_ends.push(if i > 0 && bytes[i-1] == b'\r' { i - 1 } else { i });
+        }
+        line_ends.push(text.len());
+        let wide_chars = if text.is_ascii() { Vec::new() } else {
+            text.char_indices().filter(|(_, ch)| !ch.is_ascii()).map(|(i, ch)| (i, ch.len_utf8())).collect()
+        };
+        Self { line_starts, line_ends, wide_chars, len: text.len() }
+    }
+
+    /// The number of lines in the text.  An empty text, or one ending in `\n`, still has a final empty line.
+    pub fn line_count(&self) -> usize { self.line_starts.len() }
+
+    pub(crate) fn text_len(&self) -> usize { self.len }
+
+    /// **One**-based (line, column) of a **zero**-based byte `offset`.
+    /// `offset == text.len()` is allowed, referring to the end of the text.
+    /// Returns `None` if `offset` is past the end of the text.
+    pub fn line_col(&self, offset: usize) -> Option<(usize, usize)> {
+        if offset > self.len { return None }
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line)    => line,
+            Err(next)   => next - 1,
+        };
+        Some((line + 1, offset - self.line_starts[line] + 1))
+    }
+
+    /// **Zero**-based byte offset of a **one**-based (line, column).
+    /// Returns `None` if the line doesn't exist, or if the column is past the end of the line (including its line break.)
+    pub fn offset(&self, line: usize, column: usize) -> Option<usize> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self.line_end_inclusive(line);
+        let offset = start.checked_add(column.checked_sub(1)?)?;
+        if offset < end { Some(offset) } else { None }
+    }
+
+    /// **One**-based (line, column) of a **zero**-based byte `offset`, with the column counted in `unit`s.
+    /// Returns `None` if `offset` is past the end of the text, or isn't on a [char] boundary.
+    ///
+    /// ```
+    /// # use json_spanned_value::{LineIndex, ColumnUnit};
+    /// let text = "{\"😀\": \"猫\"}";
+    /// let index = LineIndex::new(text);
+    /// let cat = text.find('猫').unwrap();
+    /// assert_eq!(index.line_col_in(cat, ColumnUnit::Byte),  Some((1, 11)));
+    /// assert_eq!(index.line_col_in(cat, ColumnUnit::Utf16), Some((1, 9)));
+    /// assert_eq!(index.line_col_in(cat, ColumnUnit::Char),  Some((1, 8)));
+    /// ```
+    pub fn line_col_in(&self, offset: usize, unit: ColumnUnit) -> Option<(usize, usize)> {
+        let (line, column) = self.line_col(offset)?;
+        if unit == ColumnUnit::Byte { return Some((line, column)) }
+
+        let start = self.line_starts[line - 1];
+        let mut units = offset - start;
+        for &(wide, len) in &self.wide_chars[self.wide_chars_from(start)..] {
+            if wide >= offset       { break }
+            if wide + len > offset  { return None } // mid-char
+            units -= len - unit.width(len);
+        }
+        Some((line, units + 1))
+    }
+
+    /// **Zero**-based byte offset of a **one**-based (line, column), with the column counted in `unit`s.
+    /// Returns `None` if the line doesn't exist, if the column is past the end of the line (including its line break),
+    /// or if the column is in the middle of a [char] (such as between the two halves of a UTF-16 surrogate pair.)
+    ///
+    /// ```
+    /// # use json_spanned_value::{self as jsv, spanned, LineIndex, ColumnUnit};
+    /// let text = "{\"😀\": [1, 2, 3]}";
+    /// let value : spanned::Value = jsv::from_str(text).unwrap();
+    /// let index = LineIndex::new(text);
+    ///
+    /// // LSP positions are zero-based, with UTF-16 characters by default
+    /// let (lsp_line, lsp_character) = (0, 11);
+    /// let offset = index.offset_in(lsp_line + 1, lsp_character + 1, ColumnUnit::Utf16).unwrap();
+    /// assert_eq!(&text[offset..], "2, 3]}");
+    /// assert_eq!(value.value_at(offset).unwrap().as_number().unwrap().as_u64(), Some(2));
+    /// ```
+    pub fn offset_in(&self, line: usize, column: usize, unit: ColumnUnit) -> Option<usize> {
+        if unit == ColumnUnit::Byte { return self.offset(line, column) }
+
+        let start   = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end     = self.line_end_inclusive(line);
+        let target  = column.checked_sub(1)?;
+        let mut pos = start;
+        let mut units = 0;
+        for &(wide, len) in &self.wide_chars[self.wide_chars_from(start)..] {
+            if wide >= end { break }
+            if target - units <= wide - pos { break } // target is in the ASCII before this char
+            units += wide - pos;
+            pos = wide;
+            let width = unit.width(len);
+            if target - units < width { return None } // mid-char
+            units += width;
+            pos += len;
+        }
+        let offset = pos + (target - units);
+        if offset < end { Some(offset) } else { None }
+    }
+
+    /// **Zero**-based byte range of a **one**-based line, excluding its `\n` or `\r\n` line break.
+    pub fn line_range(&self, line: usize) -> Option<Range<usize>> {
+        let line = line.checked_sub(1)?;
+        Some(*self.line_starts.get(line)? .. self.line_ends[line])
+    }
+
+    /// Exclusive end of a valid **one**-based line, including its line break, or one past the end of the text for the last line.
+    fn line_end_inclusive(&self, line: usize) -> usize {
+        self.line_starts.get(line).copied().unwrap_or(self.len + 1)
+    }
+
+    /// Index of the first of `wide_chars` at or after `offset`.
+    fn wide_chars_from(&self, offset: usize) -> usize {
+        match self.wide_chars.binary_search_by_key(&offset, |&(i, _)| i) {
+            Ok(i) | Err(i) => i,
+        }
+    }
+}
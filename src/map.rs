@@ -1,15 +1,22 @@
-use crate::{settings, Spanned};
+use crate::Spanned;
 
-use serde::de;
+use serde::{de, ser};
 
 use std::borrow::Borrow;
 use std::cmp::Ord;
 use std::fmt::{self, Debug, Formatter};
 use std::hash::Hash;
+use std::iter::FromIterator;
 use std::marker::PhantomData;
 
 
 
+/// [crate::Deserializer] recognizes this newtype name, and skips straight to
+/// the object (via `visit_map`), rejecting duplicate keys itself - or handling them per
+/// [Settings::duplicate_keys](crate::Settings::duplicate_keys).  Other deserializers
+/// call `visit_newtype_struct`, and duplicate keys within the object are rejected.
+pub(crate) const UNIQUE_KEYS : &str = "$__json_spanned_value::private::UniqueKeys";
+
 #[cfg(not(feature = "indexmap"))] type MapImpl<K, V> = std::collections::BTreeMap<K, V>;
 #[cfg(    feature = "indexmap" )] type MapImpl<K, V> = indexmap::IndexMap<K, V>;
 
@@ -33,7 +40,7 @@ impl<K: Hash + Ord, V> Map<K, V> {
     #[doc = "`true` if the map contains no elements."                                       ] pub fn is_empty(&self) -> bool { self.map.is_empty() }
     #[doc = "Clears the map, removing all elements."                                        ] pub fn clear(&mut self) { self.map.clear() }
     #[doc = "Inserts a key-value pair into the map, returning the replaced value, if any."  ] pub fn insert(&mut self, k: K, v: V) -> Option<V> { self.map.insert(k, v) }
-    #[doc = "Gets the given key's corresponding entry in the map for in-place manipulation."] pub fn entry(&mut self, key: K) -> Entry<K, V> { self.map.entry(key) }
+    #[doc = "Gets the given key's corresponding entry in the map for in-place manipulation."] pub fn entry(&mut self, key: K) -> Entry<'_, K, V> { self.map.entry(key) }
 
     #[doc = "`true` if the map contains the given key."                                     ] pub fn contains_key   <Q>(&self, key: &Q) -> bool                 where Q: Eq + Ord + Hash + ?Sized, K: Borrow<Q>{ self.map.contains_key(key) }
     #[doc = "Return a reference to the value stored for key, if any."                       ] pub fn get            <Q>(&self, key: &Q) -> Option<&V>           where Q: Eq + Ord + Hash + ?Sized, K: Borrow<Q>{ self.map.get(key) }
@@ -42,23 +49,35 @@ impl<K: Hash + Ord, V> Map<K, V> {
     #[doc = "Removes a key from the map, returning the previous value, if any."             ] pub fn remove         <Q>(&mut self, key: &Q) -> Option<V>        where Q: Eq + Ord + Hash + ?Sized, K: Borrow<Q>{ self.map.remove(key) }
     #[doc = "Removes a key from the map, returning the previous key/value pair, if any."    ] pub fn remove_entry   <Q>(&mut self, key: &Q) -> Option<(K, V)>   where Q: Eq + Ord + Hash + ?Sized, K: Borrow<Q>{ self.map.remove_entry(key) }
 
-    #[doc = "Gets an iterator over the keys of the map."            ] pub fn keys         (&self)     -> impl Iterator<Item = &K>             { self.map.keys().into_iter() }
-    #[doc = "Gets an iterator over the values of the map."          ] pub fn values       (&self)     -> impl Iterator<Item = &V>             { self.map.values().into_iter() }
-    #[doc = "Gets a mutable iterator over the values of the map."   ] pub fn values_mut   (&mut self) -> impl Iterator<Item = &mut V>         { self.map.values_mut().into_iter() }
+    #[doc = "Gets an iterator over the keys of the map."            ] pub fn keys         (&self)     -> impl Iterator<Item = &K>             { self.map.keys() }
+    #[doc = "Gets an iterator over the values of the map."          ] pub fn values       (&self)     -> impl Iterator<Item = &V>             { self.map.values() }
+    #[doc = "Gets a mutable iterator over the values of the map."   ] pub fn values_mut   (&mut self) -> impl Iterator<Item = &mut V>         { self.map.values_mut() }
     #[doc = "Gets an iterator over the entries of the map."         ] pub fn iter         (&self)     -> impl Iterator<Item = (&K, &V)>       { self.map.iter() }
     #[doc = "Gets a mutable iterator over the entries of the map."  ] pub fn iter_mut     (&mut self) -> impl Iterator<Item = (&K, &mut V)>   { self.map.iter_mut() }
 }
 
+impl<K: Hash + Ord, V> Default for Map<K, V> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<K: Hash + Ord, V> FromIterator<(K, V)> for Map<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self { Map { map: iter.into_iter().collect() } }
+}
+
+impl<K: Hash + Ord, V> Extend<(K, V)> for Map<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) { self.map.extend(iter) }
+}
+
 impl<'a, K: Hash + Ord + 'a, V: 'a> IntoIterator for &'a Map<K, V> {
     type Item = (&'a K, &'a V);
     type IntoIter = <&'a MapImpl<K, V> as IntoIterator>::IntoIter;
-    fn into_iter(self) -> Self::IntoIter { (&self.map).into_iter() }
+    fn into_iter(self) -> Self::IntoIter { self.map.iter() }
 }
 
 impl<'a, K: Hash + Ord + 'a, V: 'a> IntoIterator for &'a mut Map<K, V> {
     type Item = (&'a K, &'a mut V);
     type IntoIter = <&'a mut MapImpl<K, V> as IntoIterator>::IntoIter;
-    fn into_iter(self) -> Self::IntoIter { (&mut self.map).into_iter() }
+    fn into_iter(self) -> Self::IntoIter { self.map.iter_mut() }
 }
 
 impl<K: Hash + Ord, V> IntoIterator for Map<K, V> {
@@ -70,13 +89,13 @@ impl<K: Hash + Ord, V> IntoIterator for Map<K, V> {
 impl<'a, K: Hash + Ord + 'a, V: 'a> IntoIterator for &'a Spanned<Map<K, V>> {
     type Item = (&'a K, &'a V);
     type IntoIter = <&'a MapImpl<K, V> as IntoIterator>::IntoIter;
-    fn into_iter(self) -> Self::IntoIter { (&self.get_ref().map).into_iter() }
+    fn into_iter(self) -> Self::IntoIter { self.get_ref().map.iter() }
 }
 
 impl<'a, K: Hash + Ord + 'a, V: 'a> IntoIterator for &'a mut Spanned<Map<K, V>> {
     type Item = (&'a K, &'a mut V);
     type IntoIter = <&'a mut MapImpl<K, V> as IntoIterator>::IntoIter;
-    fn into_iter(self) -> Self::IntoIter { (&mut self.get_mut().map).into_iter() }
+    fn into_iter(self) -> Self::IntoIter { self.get_mut().map.iter_mut() }
 }
 
 impl<K: Hash + Ord, V> IntoIterator for Spanned<Map<K, V>> {
@@ -85,16 +104,24 @@ impl<K: Hash + Ord, V> IntoIterator for Spanned<Map<K, V>> {
     fn into_iter(self) -> Self::IntoIter { self.into_inner().map.into_iter() }
 }
 
+impl<K: Hash + Ord + ser::Serialize, V: ser::Serialize> ser::Serialize for Map<K, V> {
+    /// Entries are written in the map's iteration order: sorted by key, or insertion order with the `preserve_order` feature.
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
 impl<'de, K: Debug + Hash + Ord + de::Deserialize<'de>, V: de::Deserialize<'de>> de::Deserialize<'de> for Map<K, V> {
     fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
-        struct MapVisitor<'de, K: Ord + de::Deserialize<'de>, V: de::Deserialize<'de>>(PhantomData<(&'de (), K, V)>);
+        struct MapVisitor<'de, K: Ord + de::Deserialize<'de>, V: de::Deserialize<'de>>(bool, PhantomData<(&'de (), K, V)>);
         impl<'de, K: Debug + Hash + Ord + de::Deserialize<'de>, V: de::Deserialize<'de>> de::Visitor<'de> for MapVisitor<'de, K, V> {
             type Value = Map<K, V>;
             fn expecting(&self, formatter: &mut Formatter) -> fmt::Result { formatter.write_str("a JSON object") }
+            fn visit_newtype_struct<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> { deserializer.deserialize_any(MapVisitor(true, PhantomData)) }
             fn visit_map<MA: de::MapAccess<'de>>(self, mut visitor: MA) -> Result<Self::Value, MA::Error> {
                 let mut values = Map::new();
                 while let Some(key) = visitor.next_key()? {
-                    if !settings().map_or(false, |s| s.allow_duplicate_keys) && values.contains_key(&key) {
+                    if self.0 && values.contains_key(&key) {
                         return Err(de::Error::custom(format!("Duplicate field: {:?}", key)));
                     }
                     let value = visitor.next_value()?;
@@ -104,6 +131,6 @@ impl<'de, K: Debug + Hash + Ord + de::Deserialize<'de>, V: de::Deserialize<'de>>
             }
         }
 
-        deserializer.deserialize_any(MapVisitor::<'de, K, V>(PhantomData))
+        deserializer.deserialize_newtype_struct(UNIQUE_KEYS, MapVisitor::<'de, K, V>(false, PhantomData))
     }
 }
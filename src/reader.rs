@@ -1,150 +1,220 @@
-use crate::Shared;
+use std::io;
+use std::ops::Deref;
 
-use std::convert::*;
-use std::io::{self, Read};
-use std::sync::{Arc, atomic::Ordering::Relaxed};
 
 
+/// Either bytes borrowed from the original input for `'b`, or bytes that had
+/// to be copied/unescaped into scratch space only valid for `'c`.
+pub(crate) enum Reference<'b, 'c, T: ?Sized> {
+    Borrowed(&'b T),
+    Copied(&'c T),
+}
+
+impl<'b, 'c, T: ?Sized> Deref for Reference<'b, 'c, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        match self {
+            Reference::Borrowed(b) => b,
+            Reference::Copied(c) => c,
+        }
+    }
+}
+
+
+
+/// A source of bytes for [Deserializer](crate::Deserializer), with enough
+/// lookahead to identify comments and token boundaries.
+///
+/// This trait is sealed - it's only implemented by [SliceRead] and [IoRead].
+pub trait Read<'de>: private::Sealed {
+    /// **Zero**-based byte offset of the next unconsumed byte.
+    #[doc(hidden)] fn offset(&self) -> usize;
+
+    /// Peek `n` bytes past the next unconsumed byte without consuming anything.
+    #[doc(hidden)] fn peek_at(&mut self, n: usize) -> Option<u8>;
+
+    /// Peek the next unconsumed byte without consuming it.
+    #[doc(hidden)] fn peek(&mut self) -> Option<u8> { self.peek_at(0) }
+
+    /// All bytes currently buffered from the next unconsumed byte onward, reading more first if none are.
+    /// Empty only at EOF.
+    #[doc(hidden)] fn peek_buffered(&mut self) -> &[u8];
+
+    /// Bytes currently buffered from the next unconsumed byte onward, without reading more.
+    #[doc(hidden)] fn buffered(&self) -> &[u8];
+
+    /// Consume `n` bytes that have already been [peek](Self::peek)ed.
+    #[doc(hidden)] fn discard(&mut self, n: usize);
+
+    /// One-based (line, column) of a **zero**-based byte offset, as [serde_json::Error] would report it.
+    #[doc(hidden)] fn position_of(&self, offset: usize) -> (usize, usize);
+
+    /// Raw bytes from `start` up to [offset](Self::offset), if still available in memory.
+    #[doc(hidden)] fn raw_since(&self, start: usize) -> Option<&[u8]>;
+
+    /// Raw bytes from `start` up to [offset](Self::offset), if they can be borrowed for `'de`.
+    #[doc(hidden)] fn borrow_since(&self, start: usize) -> Option<&'de [u8]>;
+
+    /// Keep bytes from `offset` onward in memory until unpinned with `None`, so the reader can be [rewound](Self::rewind) to them.
+    #[doc(hidden)] fn pin(&mut self, _offset: Option<usize>) {}
+
+    /// Go back to an earlier `offset`, which must be [pin](Self::pin)ned (or still [buffered](Self::buffered).)
+    #[doc(hidden)] fn rewind(&mut self, offset: usize);
+
+    /// The I/O error, if any, that cut the input short.
+    #[doc(hidden)] fn take_error(&mut self) -> Option<io::Error> { None }
+}
+
+
+
+/// Reads from an in-memory slice, allowing `&'de str`s to be borrowed straight out of the input.
+pub struct SliceRead<'de> {
+    slice:  &'de [u8],
+    index:  usize,
+}
+
+impl<'de> SliceRead<'de> {
+    pub(crate) fn new(slice: &'de [u8]) -> Self { Self { slice, index: 0 } }
+    pub(crate) fn new_at(slice: &'de [u8], index: usize) -> Self { Self { slice, index } }
+}
+
+impl<'de> Read<'de> for SliceRead<'de> {
+    fn offset(&self) -> usize { self.index }
+    fn peek_at(&mut self, n: usize) -> Option<u8> { self.slice.get(self.index + n).copied() }
+    fn peek_buffered(&mut self) -> &[u8] { &self.slice[self.index..] }
+    fn buffered(&self) -> &[u8] { &self.slice[self.index..] }
+    fn discard(&mut self, n: usize) { self.index = (self.index + n).min(self.slice.len()) }
+    fn rewind(&mut self, offset: usize) { self.index = offset }
+
+    fn position_of(&self, offset: usize) -> (usize, usize) {
+        let before = &self.slice[..offset.min(self.slice.len())];
+        let start_of_line = before.iter().rposition(|b| *b == b'\n').map_or(0, |n| n + 1);
+        let line = 1 + before[..start_of_line].iter().filter(|b| **b == b'\n').count();
+        (line, offset - start_of_line + 1)
+    }
 
-/// A specialized [io::Read]er designed to identify JSON token boundaries and
-/// make their position accessible.  This results in some very odd design
-/// choices.
-pub(crate) struct Reader<B: AsRef<[u8]>> {
-    buf:    B,
-    shared: Arc<Shared>,
-    mode:   Mode,
+    fn raw_since(&self, start: usize) -> Option<&[u8]> { self.slice.get(start..self.index) }
+    fn borrow_since(&self, start: usize) -> Option<&'de [u8]> { self.slice.get(start..self.index) }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-enum Mode {
-    Normal,
-    String,
-    StringSlash,
 
-    InSingleLineComment,
-    InMultiLineComment,
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for super::SliceRead<'_> {}
+    impl<R: std::io::Read> Sealed for super::IoRead<R> {}
+    impl<R> Sealed for super::NoBorrow<R> {}
 }
 
-impl<B: AsRef<[u8]>> Reader<B> {
-    pub(crate) fn new(buf: B, shared: Arc<Shared>) -> Self {
-        let mut r = Self { buf, shared, mode: Mode::Normal };
-        r.advance_start_from(0);
-        r
+
+
+/// Wraps another [Read], but never lends out borrowed data.  Used when the
+/// underlying buffer doesn't actually live for `'de`, such as the owned `B`
+/// of a [StreamDeserializer](crate::StreamDeserializer).
+pub(crate) struct NoBorrow<R>(pub(crate) R);
+
+impl<'de, 'r, R: Read<'r>> Read<'de> for NoBorrow<R> {
+    fn offset(&self) -> usize { self.0.offset() }
+    fn peek_at(&mut self, n: usize) -> Option<u8> { self.0.peek_at(n) }
+    fn peek_buffered(&mut self) -> &[u8] { self.0.peek_buffered() }
+    fn buffered(&self) -> &[u8] { self.0.buffered() }
+    fn discard(&mut self, n: usize) { self.0.discard(n) }
+    fn pin(&mut self, offset: Option<usize>) { self.0.pin(offset) }
+    fn rewind(&mut self, offset: usize) { self.0.rewind(offset) }
+    fn position_of(&self, offset: usize) -> (usize, usize) { self.0.position_of(offset) }
+    fn raw_since(&self, start: usize) -> Option<&[u8]> { self.0.raw_since(start) }
+    fn borrow_since(&self, _start: usize) -> Option<&'de [u8]> { None }
+    fn take_error(&mut self) -> Option<io::Error> { self.0.take_error() }
+}
+
+
+
+/// Reads from an [io::Read], buffering enough lookahead to find token
+/// boundaries and comments.  Strings are always copied, never borrowed.
+pub struct IoRead<R: io::Read> {
+    reader:     R,
+    chunk:      Box<[u8]>,
+    buf:        Vec<u8>,
+    /// Index of the next unconsumed byte within `buf`
+    index:      usize,
+    /// Absolute byte offset of `buf[0]`
+    base:       usize,
+    /// Number of `\n`s in bytes dropped from the front of `buf`
+    lines:      usize,
+    /// Absolute byte offset just past the last `\n` dropped from the front of `buf`
+    line_start: usize,
+    /// Absolute byte offset of the first byte that must not be dropped from the front of `buf`, if any
+    pinned:     Option<usize>,
+    eof:        bool,
+    error:      Option<io::Error>,
+}
+
+const IO_CHUNK : usize = 8 * 1024;
+
+impl<R: io::Read> IoRead<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self { reader, chunk: vec![0; IO_CHUNK].into_boxed_slice(), buf: Vec::new(), index: 0, base: 0, lines: 0, line_start: 0, pinned: None, eof: false, error: None }
     }
 
-    pub(crate) fn advance_start_from(&mut self, mut pos: usize) {
-        assert_eq!(self.mode, Mode::Normal);
-        let shared = &*self.shared;
-        let src = self.buf.as_ref();
-
-        // We often need to seek "ahead" to find the start of the next token.
-        // Doing so in here as an I/O side effect is very odd, but avoids
-        // the need to statically store a reference to &'nonstatic [u8].
-        // 
-        // NOTE WELL:  shared.start == shared.pos - 1 is legal and common!  This
-        // means arrays, strings, etc. all include the opening character.
-
-        if shared.start_pos.load(Relaxed) > pos { return; } // Avoid O(nn) behavior for "     ..."
-
-        while let Some(ch) = src.get(pos) {
-            if b": \r\n\t".contains(ch) {
-                // Whitespace or ":" that's either not part of a token, or mid-string where we don't care
-                pos += 1;
-            } else if *ch == b'/' && shared.settings.allow_comments {
-                // Probably a comment
-                match src.get(pos+1) {
-                    Some(b'/') => {
-                        pos += 2; // b"//"
-                        while let Some(ch) = src.get(pos) {
-                            pos += 1;
-                            if *ch == b'\n' { break }
-                        }
-                    },
-                    Some(b'*') => {
-                        pos += 2; // b"/*"
-                        while let Some(ch) = src.get(pos) {
-                            if *ch == b'*' && src.get(pos+1) == Some(&b'/') {
-                                pos += 2;
-                                break;
-                            } else {
-                                pos += 1;
-                            }
-                        }
-                    },
-                    _other => break,
+    fn fill(&mut self) {
+        if self.eof { return }
+
+        // Drop fully consumed (and unpinned) bytes, remembering enough to still report line numbers
+        let consumed = self.pinned.map_or(self.index, |pinned| self.index.min(pinned - self.base));
+        if consumed >= IO_CHUNK {
+            for (i, b) in self.buf[..consumed].iter().enumerate() {
+                if *b == b'\n' {
+                    self.lines += 1;
+                    self.line_start = self.base + i + 1;
                 }
-            } else {
-                break;
             }
+            self.buf.drain(..consumed);
+            self.base += consumed;
+            self.index -= consumed;
         }
 
-        shared.start_pos.store(pos, Relaxed);
-        shared.start_ch.store(*src.get(pos).unwrap_or(&b'\0'), Relaxed);
+        loop {
+            match self.reader.read(&mut self.chunk) {
+                Ok(0) => { self.eof = true; return },
+                Ok(n) => { self.buf.extend_from_slice(&self.chunk[..n]); return },
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => { self.error = Some(err); self.eof = true; return },
+            }
+        }
     }
 }
 
-impl<B: AsRef<[u8]>> Read for Reader<B> {
-    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
-        if out.is_empty() { return Ok(0) }
-
-        // serde_json is kind enough to only ever request a single byte at
-        // a time.  Since serde_json is the only thing that should be using this
-        // reader, we simplify logic some by only ever returning a single byte
-        // at a time, even if more were available.  This also lets us accurately
-        // track serde_json's exact cursor position.
-
-        let src = self.buf.as_ref();
-        let pos = self.shared.pos.load(Relaxed);
-        let mut ch = if let Some(n) = src.get(pos) { *n } else { return Ok(0) };
-        // The current seek position is used to determine where many tokens end
-        self.shared.pos.store(pos + 1, Relaxed);
-
-        // Possibly skip comments and trailing commas.  To preserve
-        // serde_json::Error line/column -> byte offset mappings, we do so by
-        // replacing said content with whitespace.
-
-        match self.mode {
-            Mode::Normal => {
-                self.advance_start_from(pos);
-                let src = self.buf.as_ref();
-                match ch {
-                    b'\"' => self.mode = Mode::String,
-                    b',' if self.shared.settings.allow_trailing_comma => {
-                        self.advance_start_from(pos+1);
-                        match self.shared.start_ch.load(Relaxed) {
-                            b']'    => ch = b' ',
-                            b'}'    => ch = b' ',
-                            _other  => {},
-                        }
-                    },
-                    b'/' if self.shared.settings.allow_comments => {
-                        ch = b' ';
-                        match src.get(pos+1) {
-                            Some(b'/')  => self.mode = Mode::InSingleLineComment,
-                            Some(b'*')  => self.mode = Mode::InMultiLineComment,
-                            _other      => ch = b'/', // Okay, not actually a comment I guess
-                        }
-                    },
-                    _other => {},
-                }
-            },
-            Mode::String => match ch {
-                b'\\'   => self.mode = Mode::StringSlash,
-                b'\"'   => self.mode = Mode::Normal,
-                _other  => {},
-            },
-            Mode::StringSlash => self.mode = Mode::String, // \n, \u1234, ...
-            Mode::InSingleLineComment => match ch {
-                b'\n' => self.mode = Mode::Normal,
-                _other => ch = b' ',
-            },
-            Mode::InMultiLineComment => {
-                if ch == b'/' && src[pos-1] == b'*' { self.mode = Mode::Normal }
-                ch = b' ';
-            },
-        }
+impl<'de, R: io::Read> Read<'de> for IoRead<R> {
+    fn offset(&self) -> usize { self.base + self.index }
+
+    fn peek_at(&mut self, n: usize) -> Option<u8> {
+        while self.index + n >= self.buf.len() && !self.eof { self.fill(); }
+        self.buf.get(self.index + n).copied()
+    }
+
+    fn peek_buffered(&mut self) -> &[u8] {
+        if self.index >= self.buf.len() { self.fill(); }
+        &self.buf[self.index..]
+    }
 
-        out[0] = ch;
-        Ok(1)
+    fn buffered(&self) -> &[u8] { &self.buf[self.index..] }
+    fn discard(&mut self, n: usize) { self.index = (self.index + n).min(self.buf.len()) }
+    fn pin(&mut self, offset: Option<usize>) { self.pinned = offset }
+    fn rewind(&mut self, offset: usize) { self.index = offset - self.base }
+
+    fn position_of(&self, offset: usize) -> (usize, usize) {
+        let (mut line, mut line_start) = (self.lines + 1, self.line_start);
+        let end = offset.saturating_sub(self.base).min(self.buf.len());
+        for (i, b) in self.buf[..end].iter().enumerate() {
+            if *b == b'\n' {
+                line += 1;
+                line_start = self.base + i + 1;
+            }
+        }
+        (line, offset.saturating_sub(line_start) + 1)
     }
+
+    fn raw_since(&self, _start: usize) -> Option<&[u8]> { None }
+    fn borrow_since(&self, _start: usize) -> Option<&'de [u8]> { None }
+    fn take_error(&mut self) -> Option<io::Error> { self.error.take() }
 }
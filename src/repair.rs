@@ -0,0 +1,269 @@
+use crate::{spanned, Error, Result, Value};
+use crate::value_deserializer::{Node, SpannedAccess};
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Visitor};
+use serde::de::value::BorrowedStrDeserializer;
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+
+
+/// Nodes (and object keys) of the parsed [spanned::Value] tree are identified by address, since the tree stays put for as long as
+/// its [Repairs] are used.  Byte ranges won't do: a [Placeholder] for a missing field shares the range of the object it's missing from.
+type Id = usize;
+
+fn id<T>(node: &T) -> Id { node as *const T as usize }
+
+/// Changes made to a document by [crate::from_str_collect_errors] to keep deserializing past errors.
+#[derive(Default)]
+pub(crate) struct Repairs {
+    /// Values that failed to deserialize, replaced with a [Placeholder].
+    placeholders:   HashSet<Id>,
+    /// Object entries (by key) or array elements (by value) removed from their parent.
+    skipped:        HashSet<Id>,
+    /// Missing fields to fill with a [Placeholder], by object.
+    added:          HashMap<Id, Vec<&'static str>>,
+    /// Arrays and objects with skipped or added children, whose errors are likely side effects.
+    changed:        HashSet<Id>,
+}
+
+impl Repairs {
+    pub(crate) fn is_placeholder(&self, node: &spanned::Value) -> bool { self.placeholders.contains(&id(node)) }
+    pub(crate) fn is_skipped<T>(&self, key_or_element: &T) -> bool { self.skipped.contains(&id(key_or_element)) }
+    pub(crate) fn added(&self, node: &spanned::Value) -> &[&'static str] { self.added.get(&id(node)).map_or(&[], |added| &added[..]) }
+
+    /// Repair whatever caused `err`.  Returns `Some(true)` if `err` is worth reporting,
+    /// `Some(false)` if it was a side effect of an earlier repair, or `None` if there's nothing left to try.
+    ///
+    /// Failing values are replaced with a [Placeholder], and removed from their parent if even that fails.
+    fn repair(&mut self, root: &spanned::Value, err: &Error) -> Option<bool> {
+        match self.locate(root, err)? {
+            Location::Key { entry, parent } => {
+                if !self.skipped.insert(entry) { return None }
+                self.changed.insert(parent);
+                Some(true)
+            },
+            Location::Value { node, skip, parent } => {
+                let here = id(node);
+                let present = |field| matches!(node.as_object(), Some(o) if o.contains_key(field));
+                let derived = match err.missing_field() {
+                    Some(field) if !present(field) && !self.added(node).contains(&field) => {
+                        self.added.entry(here).or_default().push(field);
+                        self.changed.insert(here);
+                        return Some(true);
+                    },
+                    Some(_field)    => true, // we removed it, or its placeholder failed
+                    None            => self.changed.contains(&here),
+                };
+                if self.placeholders.insert(here) { return Some(!derived) }
+                if !self.skipped.insert(skip?) { return None }
+                self.changed.extend(parent);
+                Some(false)
+            },
+        }
+    }
+
+    /// Find what `err` is blaming: follow its [pointer](Error::pointer) as far as the (repaired) document goes - any further
+    /// is a missing field we added - then any descendant or key its [range](Error::range) singles out, as errors raised with
+    /// an explicit range (like [Spanned::error](crate::Spanned::error)) may.
+    fn locate<'a>(&self, root: &'a spanned::Value, err: &Error) -> Option<Location<'a>> {
+        let range = err.range()?;
+        let mut pointer = err.pointer().split('/').skip(1).map(|segment| segment.replace("~1", "/").replace("~0", "~"));
+        let (mut node, mut skip, mut parent) = (root, None, None);
+        loop {
+            let within = |child: &spanned::Value| child.range() != node.range() && child.start() <= range.start && range.end <= child.end();
+            let child = match (node.get_ref(), pointer.next()) {
+                (Value::Array(a), Some(segment))    => segment.parse().ok().and_then(|i: usize| a.get(i)).map(|v| (id(v), v)),
+                (Value::Object(o), Some(segment))   => o.get_key_value(segment.as_str()).map(|(k, v)| (id(k), v)),
+                (Value::Array(a), None)             => a.iter().find(|v| within(v)).map(|v| (id(v), v)),
+                (Value::Object(o), None)            => {
+                    if let Some((key, _)) = o.iter().find(|(k, _)| k.range() == range && !self.is_skipped(*k)) {
+                        return Some(Location::Key { entry: id(key), parent: id(node) });
+                    }
+                    o.iter().find(|(_, v)| within(v)).map(|(k, v)| (id(k), v))
+                },
+                _scalar                             => None,
+            };
+            match child.filter(|(entry, _)| !self.skipped.contains(entry)) {
+                Some((entry, child))    => { parent = Some(id(node)); skip = Some(entry); node = child; },
+                None                    => return Some(Location::Value { node, skip, parent }),
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Location<'a> {
+    /// An object key, which is repaired by skipping the `entry`.
+    Key { entry: Id, parent: Id },
+    /// A value, which can be removed from its `parent` by skipping `skip` (both `None` for the root.)
+    Value { node: &'a spanned::Value, skip: Option<Id>, parent: Option<Id> },
+}
+
+/// Deserialize `value` as a `T`, repairing the document and trying again after each error until `T` can be built, or there's nothing left to repair.
+pub(crate) fn deserialize_collecting_errors<T: DeserializeOwned>(value: &spanned::Value) -> (Option<T>, Vec<Error>) {
+    let mut repairs = Repairs::default();
+    let mut errors = Vec::new();
+    loop {
+        let err = match T::deserialize(Node::new(value, Some(&repairs))) {
+            Ok(value)   => return (Some(value), errors),
+            Err(err)    => err,
+        };
+        match repairs.repair(value, &err) {
+            Some(true)  => errors.push(err),
+            Some(false) => {},
+            None        => {
+                if errors.is_empty() { errors.push(err) }
+                return (None, errors);
+            },
+        }
+    }
+}
+
+
+
+/// Stands in for a value that failed to deserialize (or a missing field), using the simplest value of whatever type is requested:
+/// `0`, `false`, `""`, `None`, an empty sequence or map, a struct of placeholders, or the first variant of an enum.
+#[derive(Clone)]
+pub(crate) struct Placeholder {
+    /// The range of the value being replaced (or the object missing a field), for [Spanned](crate::Spanned)s and errors.
+    range: Range<usize>,
+}
+
+impl Placeholder {
+    pub(crate) fn new(range: Range<usize>) -> Self { Self { range } }
+
+    fn at_node(&self, mut err: Error) -> Error {
+        if err.range().is_none() { err.set_range(self.range.clone()); }
+        err
+    }
+}
+
+macro_rules! visit_placeholder {
+    ($( $method:ident => $visit:ident ( $($value:expr)? ), )*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> { visitor.$visit($($value)?).map_err(|err| self.at_node(err)) }
+    )*};
+}
+
+impl<'de> de::Deserializer<'de> for Placeholder {
+    type Error = Error;
+
+    visit_placeholder! {
+        deserialize_any         => visit_unit(),
+        deserialize_bool        => visit_bool(false),
+        deserialize_i8          => visit_i64(0),
+        deserialize_i16         => visit_i64(0),
+        deserialize_i32         => visit_i64(0),
+        deserialize_i64         => visit_i64(0),
+        deserialize_i128        => visit_i64(0),
+        deserialize_u8          => visit_u64(0),
+        deserialize_u16         => visit_u64(0),
+        deserialize_u32         => visit_u64(0),
+        deserialize_u64         => visit_u64(0),
+        deserialize_u128        => visit_u64(0),
+        deserialize_f32         => visit_f64(0.0),
+        deserialize_f64         => visit_f64(0.0),
+        deserialize_char        => visit_char('\0'),
+        deserialize_str         => visit_borrowed_str(""),
+        deserialize_string      => visit_borrowed_str(""),
+        deserialize_identifier  => visit_borrowed_str(""),
+        deserialize_bytes       => visit_borrowed_bytes(&[]),
+        deserialize_byte_buf    => visit_borrowed_bytes(&[]),
+        deserialize_option      => visit_none(),
+        deserialize_unit        => visit_unit(),
+        deserialize_ignored_any => visit_unit(),
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> { self.deserialize_unit(visitor) }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V) -> Result<V::Value> {
+        if name == spanned::NAME {
+            visitor.visit_map(SpannedAccess::new(self.range.clone(), self))
+        } else {
+            visitor.visit_newtype_struct(self)
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> { self.deserialize_tuple(0, visitor) }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(PlaceholderSeq { placeholder: self.clone(), len }).map_err(|err| self.at_node(err))
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, len: usize, visitor: V) -> Result<V::Value> { self.deserialize_tuple(len, visitor) }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> { self.deserialize_struct("", &[], visitor) }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        visitor.visit_map(PlaceholderFields { placeholder: self.clone(), fields: fields.iter() }).map_err(|err| self.at_node(err))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        match variants.first() {
+            Some(variant)   => visitor.visit_enum(PlaceholderVariant { placeholder: self.clone(), variant }).map_err(|err| self.at_node(err)),
+            None            => Err(self.at_node(<Error as de::Error>::custom("enum has no variants"))),
+        }
+    }
+}
+
+struct PlaceholderSeq {
+    placeholder:    Placeholder,
+    len:            usize,
+}
+
+impl<'de> de::SeqAccess<'de> for PlaceholderSeq {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.len == 0 { return Ok(None) }
+        self.len -= 1;
+        seed.deserialize(self.placeholder.clone()).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> { Some(self.len) }
+}
+
+struct PlaceholderFields {
+    placeholder:    Placeholder,
+    fields:         std::slice::Iter<'static, &'static str>,
+}
+
+impl<'de> de::MapAccess<'de> for PlaceholderFields {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match self.fields.next() {
+            Some(field) => seed.deserialize(BorrowedStrDeserializer::new(field)).map(Some),
+            None        => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> { seed.deserialize(self.placeholder.clone()) }
+
+    fn size_hint(&self) -> Option<usize> { Some(self.fields.len()) }
+}
+
+struct PlaceholderVariant {
+    placeholder:    Placeholder,
+    variant:        &'static str,
+}
+
+impl<'de> de::EnumAccess<'de> for PlaceholderVariant {
+    type Error = Error;
+    type Variant = Placeholder;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Placeholder)> {
+        let variant = seed.deserialize(BorrowedStrDeserializer::<Error>::new(self.variant))?;
+        Ok((variant, self.placeholder))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Placeholder {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> { Ok(()) }
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> { seed.deserialize(self) }
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> { de::Deserializer::deserialize_tuple(self, len, visitor) }
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value> { de::Deserializer::deserialize_struct(self, "", fields, visitor) }
+}
@@ -1,27 +1,75 @@
 /// Deserialization/parsing settings
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default)]
 pub struct Settings {
-    /// Allow duplicate JSON object/map keys when deserializing [Map](crate::Map)s, such as: `{"a": 1, "a": 2}`.  Only one value will be retained.<br>
+    /// Allow duplicate JSON object/map keys such as: `{"a": 1, "a": 2}`, keeping the last value.
+    /// If [duplicate_keys](Self::duplicate_keys) is `None`, `true` is the same as `Some(`[DuplicateKeys::KeepLast]`)`.
+    /// If [duplicate_keys](Self::duplicate_keys) is set, it wins and this is ignored.<br>
     /// **default: false**
+    #[deprecated = "set duplicate_keys: Some(DuplicateKeys::KeepLast) instead"]
     pub allow_duplicate_keys: bool,
 
-    /// Allow trailing commas when deserializing an array such as `[1, 2, 3,]` or object such as `{"a", 1, "b": 2,}`.
+    /// What to do about duplicate JSON object keys such as: `{"a": 1, "a": 2}`.
+    ///
+    /// `None` only checks [Map](crate::Map)s and [Value](crate::Value)s, which fail with a [DuplicateKeys::Error] -
+    /// other maps keep the last value, and structs fail with serde's own `duplicate field` error, like [serde_json] does.
+    ///
+    /// `Some` policy applies to [Map](crate::Map)s, [Value](crate::Value)s, and any other maps or structs deserialized by [Deserializer](crate::Deserializer),
+    /// but not to the contents of values that are skipped entirely (such as unknown struct fields.)<br>
+    /// **default: None**
+    pub duplicate_keys: Option<DuplicateKeys>,
+
+    /// Allow trailing commas when deserializing an array such as `[1, 2, 3,]` or object such as `{"a", 1, "b": 2,}`.<br>
+    /// **default: false**
     pub allow_trailing_comma: bool,
 
-    /// Allow `// single line` or `/* block */` comments.
+    /// Allow `// single line` or `/* block */` comments.<br>
+    /// **default: false**
     pub allow_comments: bool,
 
+    /// Parse [JSON5](https://json5.org/): unquoted `identifier` keys, `'single quoted'` strings with line continuations,
+    /// hexadecimal numbers like `0xFF`, leading/trailing decimal points like `.5` or `5.`, explicit `+` signs,
+    /// `Infinity` / `NaN`, and additional whitespace.  Implies `allow_comments` and `allow_trailing_comma`.
+    ///
+    /// JSON numbers can't be infinite or NaN, so [Value](crate::Value)s (like [serde_json::Value]s) hold `Infinity` and `NaN` as `null` -
+    /// still spanning the original literal.  Deserialize an `f32` or `f64` to keep them.<br>
+    /// **default: false**
+    pub allow_json5: bool,
+
     #[doc(hidden)] pub _non_exhaustive: ()
 }
 
-impl Default for Settings {
-    fn default() -> Self {
-        Self {
-            allow_duplicate_keys:   false,
-            allow_trailing_comma:   false,
-            allow_comments:         false,
+/// How to handle an object key that was already used earlier in the same object, such as the second `"a"` in `{"a": 1, "a": 2}`.
+///
+/// ```
+/// # use json_spanned_value::{self as jsv, DuplicateKeys, Settings};
+/// # use std::collections::HashMap;
+/// let json = "{\"a\": 1, \"a\": 2}";
+/// let first = Settings { duplicate_keys: Some(DuplicateKeys::KeepFirst), ..Settings::default() };
+/// let error = Settings { duplicate_keys: Some(DuplicateKeys::Error),     ..Settings::default() };
+/// assert_eq!(jsv::from_str_with_settings::<HashMap<String, u32>>(json, &first).unwrap()["a"], 1);
+/// assert_eq!(jsv::from_str::<HashMap<String, u32>>(json).unwrap()["a"], 2); // only Maps and Values are checked by default
+///
+/// let err = jsv::from_str_with_settings::<HashMap<String, u32>>(json, &error).unwrap_err();
+/// assert_eq!(err.to_string(), "duplicate key `a` at line 1 column 13");
+/// assert_eq!(err.range(), Some(9 .. 12));
+/// assert_eq!(err.duplicate_of(), Some(1 .. 4));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DuplicateKeys {
+    /// Fail with an [Error](crate::Error) spanning the repeated key, and [pointing at](crate::Error::duplicate_of) the original.
+    Error,
+
+    /// Keep the first value, skipping any later ones.
+    KeepFirst,
+
+    /// Keep the last value.  Earlier values are still parsed, but discarded.
+    ///
+    /// Structs reject repeated fields themselves, so the outermost struct's object is scanned ahead of time for the earlier values to skip.
+    /// When reading from an [io::Read](std::io::Read) (e.g. [from_reader](crate::from_reader)), that means buffering the entire object in memory
+    /// until the scan is done, however large it is - deserialize a map or [Value](crate::Value) instead to keep memory use bounded.
+    KeepLast,
 
-            _non_exhaustive:        ()
-        }
-    }
+    /// Keep the last value, like [KeepLast](Self::KeepLast) (buffering structs read from an [io::Read](std::io::Read) the same way),
+    /// but also record every repeated key - see [Deserializer::take_duplicate_keys](crate::Deserializer::take_duplicate_keys).
+    Collect,
 }
@@ -5,21 +5,49 @@
 //! [Obj]\[[ect](Object)\],
 //! [Span], [Array]}
 
-use super::{start, end};
-
 use serde::de::*;
+use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer, EnumAccessDeserializer, StringDeserializer};
+use serde::ser::{Serialize, Serializer};
 
 use std::borrow::Borrow;
+use std::cell::Cell;
 use std::cmp::{PartialEq, Eq, PartialOrd, Ord, Ordering};
 use std::convert::*;
 use std::fmt::{self, Debug, Display, Formatter};
 use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
 use std::ops::{Deref, Range};
 
 
 
 /// A value with start/end position information.
 /// Can wrap arbitrary [Deserialize]able JSON values, not just basic JSON types.
+///
+/// # `#[serde(flatten)]`, `#[serde(untagged)]`, and `#[serde(tag = "...")]`
+///
+/// serde buffers the input of these into a private, position-less representation
+/// before deserializing your types from that, so any [Spanned] inside them gets a
+/// `0 .. 0` span.  Instead, buffer into a [Value] yourself - which keeps every span -
+/// then pick the type to deserialize, and use [crate::from_value] or `T::deserialize(&value)`:
+///
+/// ```
+/// # use json_spanned_value::{self as jsv, spanned, Spanned};
+/// # use serde::Deserialize;
+/// #[derive(Deserialize)] struct Http { port: Spanned<u16> }
+/// #[derive(Deserialize)] struct File { path: spanned::String }
+/// enum Backend { Http(Http), File(File) }
+///
+/// let json = "{\"type\": \"http\", \"port\": 8080}";
+/// let value : spanned::Value = jsv::from_str(json).unwrap();
+/// let backend = match value.pointer("/type").and_then(|t| t.as_string()) {
+///     Some("http")    => Backend::Http(Http::deserialize(&value).unwrap()),
+///     Some("file")    => Backend::File(File::deserialize(&value).unwrap()),
+///     _other          => panic!("unknown backend type"),
+/// };
+/// if let Backend::Http(http) = backend {
+///     assert_eq!(&json[http.port.range()], "8080");
+/// }
+/// ```
 #[derive(Clone)]
 pub struct Spanned<V> {
     pub(crate) start:  usize,
@@ -29,21 +57,44 @@ pub struct Spanned<V> {
 
 impl<V> Spanned<V> {
     /// Get the starting byte offset (inclusive) of this value.
-    /// Likely `0` unless loaded through [crate::from_*](crate::from_slice).
+    /// Likely `0` unless loaded through [crate::from_*](crate::from_slice) or [crate::Deserializer].
     pub fn start(&self) -> usize { self.start }
 
     /// Get the ending byte offset (non-inclusive) of this value.
-    /// Likely `0` unless loaded through [crate::from_*](crate::from_slice).
+    /// Likely `0` unless loaded through [crate::from_*](crate::from_slice) or [crate::Deserializer].
     pub fn end(&self) -> usize { self.end }
 
     /// Get the start .. end byte offset of this value as a (start, end) tuple.
-    /// Likely `(0, 0)` unless loaded through [crate::from_*](crate::from_slice).
+    /// Likely `(0, 0)` unless loaded through [crate::from_*](crate::from_slice) or [crate::Deserializer].
     pub fn span(&self) -> (usize, usize) { (self.start, self.end) }
 
     /// Get the start .. end byte offset of this value as a start .. end [Range].
-    /// Likely `0 .. 0` unless loaded through [crate::from_*](crate::from_slice).
+    /// Likely `0 .. 0` unless loaded through [crate::from_*](crate::from_slice) or [crate::Deserializer].
     pub fn range(&self) -> Range<usize> { self.start .. self.end }
 
+    /// Create a [de::Error](Error) for this value's [range](Self::range), such as for a value that failed validation.
+    /// See [crate::error_at].
+    ///
+    /// Deserializing a [Spanned] is also how a hand written [Deserialize] impl or `deserialize_with` function learns where it is:
+    ///
+    /// ```
+    /// # use json_spanned_value::{self as jsv, Spanned};
+    /// # use serde::{Deserialize, Deserializer};
+    /// fn port<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+    ///     let port = Spanned::<u16>::deserialize(deserializer)?;
+    ///     if *port < 1024 { return Err(port.error("port out of range")) }
+    ///     Ok(port.into_inner())
+    /// }
+    ///
+    /// #[derive(Deserialize)] struct Server { #[serde(deserialize_with = "port")] port: u16 }
+    ///
+    /// let json = "{\"port\": 80}";
+    /// let err = jsv::from_str::<Server>(json).err().unwrap();
+    /// assert_eq!(err.to_string(), "port out of range at line 1 column 10");
+    /// assert_eq!(&json[err.range().unwrap()], "80");
+    /// ```
+    pub fn error<E: Error>(&self, msg: impl Display) -> E { crate::error_at(self.range(), msg) }
+
     /// Get the interior value of the spanned region as an owned value.
     pub fn into_inner(self) -> V { self.value }
 
@@ -61,20 +112,143 @@ impl<V>                 Deref       for Spanned<V> { fn deref(&self) -> &Self::T
 impl<R, V: AsRef<R>>    AsRef<R>    for Spanned<V> { fn as_ref(&self) -> &R { self.value.as_ref() } }
 impl<V: Debug>          Debug       for Spanned<V> { fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { self.value.fmt(fmt) } }
 impl<V: Display>        Display     for Spanned<V> { fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { self.value.fmt(fmt) } }
+impl<V: Default>        Default     for Spanned<V> { fn default() -> Self { Self::from(V::default()) } }
 impl<V>                 From<V>     for Spanned<V> { fn from(v: V) -> Self { Self { value: v, start: 0, end: 0 } } }
 impl<V: Eq>             Eq          for Spanned<V> {}
 impl<V: Ord>            Ord         for Spanned<V> { fn cmp(&self, other: &Self) -> Ordering { self.value.cmp(&other.value) } }
 impl<V: PartialEq>      PartialEq   for Spanned<V> { fn eq(&self, other: &Self) -> bool { self.value.eq(&other.value) } }
 impl<V: PartialOrd>     PartialOrd  for Spanned<V> { fn partial_cmp(&self, other: &Self) -> Option<Ordering> { self.value.partial_cmp(&other.value) } }
 impl<V: Hash>           Hash        for Spanned<V> { fn hash<H: Hasher>(&self, hasher: &mut H) { self.value.hash(hasher) } }
+impl<V: Serialize>      Serialize   for Spanned<V> { fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> { self.value.serialize(serializer) } }
+
+// [crate::Deserializer] recognizes this newtype name, and responds with a map
+// of START, VALUE, and END.  Other deserializers will generally treat it as a
+// transparent newtype, resulting in a 0 .. 0 span.
+pub(crate) const NAME   : &str = "$__json_spanned_value::private::Spanned";
+pub(crate) const START  : &str = "$__json_spanned_value::private::start";
+pub(crate) const VALUE  : &str = "$__json_spanned_value::private::value";
+pub(crate) const END    : &str = "$__json_spanned_value::private::end";
+pub(crate) const FIELDS : &[&str] = &[START, VALUE, END];
 
 impl<'de, V: Deserialize<'de>> Deserialize<'de> for Spanned<V> {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
-        let (start, start_ch)   = start().unwrap_or((0, '\0'));
-        let value               = V::deserialize(deserializer)?;
-        let end                 = end().unwrap_or(0);
-        let end                 = if "[{ntf\"".contains(start_ch) { end } else { end.saturating_sub(1) };
-        Ok(Self { start, end, value })
+        let visited = Cell::new(false);
+        deserializer.deserialize_newtype_struct(NAME, SpannedVisitor(&visited, PhantomData)).or_else(|err| {
+            // serde deserializes missing fields from a stand-in that only supports deserialize_option, and fails
+            // everything else - including our newtype - before visiting anything.  Give `Spanned<Option<T>>` its `None`.
+            // Real deserializers visit before they can fail: forwarding to visit_newtype_struct, or (like ours) parsing within visit_map.
+            if visited.get() { return Err(err) }
+            V::deserialize(MissingField(err)).map(Spanned::from)
+        })
+    }
+}
+
+struct SpannedVisitor<'a, V>(&'a Cell<bool>, PhantomData<V>);
+
+macro_rules! forward_to_value {
+    ($( $visit:ident ( $ty:ty ) => $de:expr, )*) => {$(
+        fn $visit<E: Error>(self, v: $ty) -> Result<Self::Value, E> { self.0.set(true); V::deserialize($de(v)).map(Spanned::from) }
+    )*};
+}
+
+impl<'de, V: Deserialize<'de>> Visitor<'de> for SpannedVisitor<'_, V> {
+    type Value = Spanned<V>;
+    fn expecting(&self, formatter: &mut Formatter) -> fmt::Result { formatter.write_str("a spanned value") }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        self.0.set(true);
+        match map.next_key::<Field>()? {
+            Some(Field::Start)      => {},
+            // Not our map: a foreign deserializer forwarded the newtype to deserialize_any
+            Some(Field::Other(key)) => return V::deserialize(MapAccessDeserializer::new(PrependKey { key: Some(key), map })).map(Spanned::from),
+            None                    => return V::deserialize(MapAccessDeserializer::new(map)).map(Spanned::from),
+        }
+
+        // crate::Deserializer always provides exactly START, VALUE, END - in that order - so skip checking the remaining keys
+        let start = map.next_value::<u64>()? as usize;
+        map.next_key::<IgnoredAny>()?.ok_or_else(|| A::Error::missing_field(VALUE))?;
+        let value = map.next_value::<V>()?;
+        map.next_key::<IgnoredAny>()?.ok_or_else(|| A::Error::missing_field(END))?;
+        let end = map.next_value::<u64>()? as usize;
+        Ok(Spanned { start, end, value })
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> { self.0.set(true); V::deserialize(deserializer).map(Spanned::from) }
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> { self.0.set(true); V::deserialize(deserializer).map(Spanned::from) }
+    fn visit_none<E: Error>(self) -> Result<Self::Value, E> { self.0.set(true); V::deserialize(().into_deserializer()).map(Spanned::from) }
+    fn visit_unit<E: Error>(self) -> Result<Self::Value, E> { self.0.set(true); V::deserialize(().into_deserializer()).map(Spanned::from) }
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> { self.0.set(true); V::deserialize(SeqAccessDeserializer::new(seq)).map(Spanned::from) }
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> { self.0.set(true); V::deserialize(EnumAccessDeserializer::new(data)).map(Spanned::from) }
+
+    forward_to_value! {
+        visit_bool(bool)                => IntoDeserializer::into_deserializer,
+        visit_i8(i8)                    => IntoDeserializer::into_deserializer,
+        visit_i16(i16)                  => IntoDeserializer::into_deserializer,
+        visit_i32(i32)                  => IntoDeserializer::into_deserializer,
+        visit_i64(i64)                  => IntoDeserializer::into_deserializer,
+        visit_i128(i128)                => IntoDeserializer::into_deserializer,
+        visit_u8(u8)                    => IntoDeserializer::into_deserializer,
+        visit_u16(u16)                  => IntoDeserializer::into_deserializer,
+        visit_u32(u32)                  => IntoDeserializer::into_deserializer,
+        visit_u64(u64)                  => IntoDeserializer::into_deserializer,
+        visit_u128(u128)                => IntoDeserializer::into_deserializer,
+        visit_f32(f32)                  => IntoDeserializer::into_deserializer,
+        visit_f64(f64)                  => IntoDeserializer::into_deserializer,
+        visit_char(char)                => IntoDeserializer::into_deserializer,
+        visit_str(&str)                 => IntoDeserializer::into_deserializer,
+        visit_borrowed_str(&'de str)    => value::BorrowedStrDeserializer::new,
+        visit_string(std::string::String) => IntoDeserializer::into_deserializer,
+        visit_bytes(&[u8])              => IntoDeserializer::into_deserializer,
+        visit_borrowed_bytes(&'de [u8]) => value::BorrowedBytesDeserializer::new,
+        visit_byte_buf(Vec<u8>)         => IntoDeserializer::into_deserializer,
+    }
+}
+
+enum Field { Start, Other(std::string::String) }
+
+impl<'de> Deserialize<'de> for Field {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FieldVisitor;
+        impl<'de> Visitor<'de> for FieldVisitor {
+            type Value = Field;
+            fn expecting(&self, formatter: &mut Formatter) -> fmt::Result { formatter.write_str("a string key") }
+            fn visit_str<E: Error>(self, v: &str) -> Result<Field, E> {
+                Ok(if v == START { Field::Start } else { Field::Other(v.into()) })
+            }
+        }
+        deserializer.deserialize_identifier(FieldVisitor)
+    }
+}
+
+/// Replays a key already read to identify a map as not being one of ours.
+struct PrependKey<A> {
+    key: Option<std::string::String>,
+    map: A,
+}
+
+impl<'de, A: MapAccess<'de>> MapAccess<'de> for PrependKey<A> {
+    type Error = A::Error;
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, A::Error> {
+        match self.key.take() {
+            Some(key)   => seed.deserialize(StringDeserializer::new(key)).map(Some),
+            None        => self.map.next_key_seed(seed),
+        }
+    }
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value, A::Error> { self.map.next_value_seed(seed) }
+    fn size_hint(&self) -> Option<usize> { self.map.size_hint().map(|n| n + self.key.iter().count()) }
+}
+
+/// Mirrors serde's missing field stand-in: `None` for options, and the original error for anything else.
+struct MissingField<E>(E);
+
+impl<'de, E: Error> Deserializer<'de> for MissingField<E> {
+    type Error = E;
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, E> { Err(self.0) }
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> { visitor.visit_none() }
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
     }
 }
 
@@ -105,10 +279,10 @@ impl<'de, V: Deserialize<'de>> Deserialize<'de> for Spanned<V> {
 impl Value {
     #[doc="`Some(span + ()) if self is `null`"                                     ] pub fn as_span_null    (&self) -> Option<Null>    { match  self.value { super::Value::Null      => Some(Spanned { start: self.start, end: self.end, value: () }), _ => None } }
     #[doc="`Some(span + inner)` if self is `true` or `false`"                      ] pub fn as_span_bool    (&self) -> Option<Bool>    { match  self.value { super::Value::Bool(v)   => Some(Spanned { start: self.start, end: self.end, value: v  }), _ => None } }
-    #[doc="`Some(span + &inner)` if self is a number like `123`"                   ] pub fn as_span_number  (&self) -> Option<Num>     { match &self.value { super::Value::Number(v) => Some(Spanned { start: self.start, end: self.end, value: &v }), _ => None } }
-    #[doc="`Some(span + &inner)` if self is a string like `\"asdf\"`"              ] pub fn as_span_string  (&self) -> Option<Str>     { match &self.value { super::Value::String(v) => Some(Spanned { start: self.start, end: self.end, value: &v }), _ => None } }
-    #[doc="`Some(span + &inner)` if self is an array like `[1, 2, 3]`"             ] pub fn as_span_array   (&self) -> Option<Span>    { match &self.value { super::Value::Array(v)  => Some(Spanned { start: self.start, end: self.end, value: v  }), _ => None } }
-    #[doc="`Some(span + &inner)` if self is an object like `{\"a\": 1, \"b\": 2}`" ] pub fn as_span_object  (&self) -> Option<Obj>     { match &self.value { super::Value::Object(v) => Some(Spanned { start: self.start, end: self.end, value: v  }), _ => None } }
+    #[doc="`Some(span + &inner)` if self is a number like `123`"                   ] pub fn as_span_number  (&self) -> Option<Num<'_>> { match &self.value { super::Value::Number(v) => Some(Spanned { start: self.start, end: self.end, value: v  }), _ => None } }
+    #[doc="`Some(span + &inner)` if self is a string like `\"asdf\"`"              ] pub fn as_span_string  (&self) -> Option<Str<'_>> { match &self.value { super::Value::String(v) => Some(Spanned { start: self.start, end: self.end, value: v  }), _ => None } }
+    #[doc="`Some(span + &inner)` if self is an array like `[1, 2, 3]`"             ] pub fn as_span_array   (&self) -> Option<Span<'_>> { match &self.value { super::Value::Array(v)  => Some(Spanned { start: self.start, end: self.end, value: v  }), _ => None } }
+    #[doc="`Some(span + &inner)` if self is an object like `{\"a\": 1, \"b\": 2}`" ] pub fn as_span_object  (&self) -> Option<Obj<'_>> { match &self.value { super::Value::Object(v) => Some(Spanned { start: self.start, end: self.end, value: v  }), _ => None } }
 
     // TODO: as_span_*_mut ?  how would that even work?
 
@@ -121,8 +295,8 @@ impl Value {
 
     /// Lookup a value by JSON Pointer ([RFC 6901](https://tools.ietf.org/html/rfc6901))
     pub fn pointer(&self, path: &str) -> Option<&Value> {
-        if path == "" { return Some(self) }
-        if !path.starts_with("/") { return None }
+        if path.is_empty() { return Some(self) }
+        if !path.starts_with('/') { return None }
         let mut current = self;
         let tokens = path.split('/').skip(1).map(|t| t.replace("~1", "/").replace("~0", "~"));
         for token in tokens {
@@ -137,8 +311,8 @@ impl Value {
 
     /// Lookup a value by JSON Pointer ([RFC 6901](https://tools.ietf.org/html/rfc6901))
     pub fn pointer_mut(&mut self, path: &str) -> Option<&mut Value> {
-        if path == "" { return Some(self) }
-        if !path.starts_with("/") { return None }
+        if path.is_empty() { return Some(self) }
+        if !path.starts_with('/') { return None }
         let mut current = self;
         let tokens = path.split('/').skip(1).map(|t| t.replace("~1", "/").replace("~0", "~"));
         for token in tokens {
@@ -1,21 +1,22 @@
 // https://docs.rs/serde_json/1.0.58/serde_json/de/struct.StreamDeserializer.html
 
-use crate::{Reader, Settings, Shared, SharedStack};
+use crate::{Deserializer, NoBorrow, Result, Settings, SliceRead};
 
 use serde::Deserialize;
 
-use serde_json::de::{IoRead, StreamDeserializer as BaseStreamDeserializer};
-use serde_json::{Result};
 
 use std::iter::{FusedIterator, Iterator};
-use std::sync::Arc;
+use std::marker::PhantomData;
 
 
 
 /// Iterator that deserializes a stream into multiple JSON values.
 pub struct StreamDeserializer<'de, B: AsRef<[u8]>, T: Deserialize<'de>> {
-    base:   BaseStreamDeserializer<'de, IoRead<Reader<B>>, T>,
-    shared: Arc<Shared>,
+    buffer: B,
+    settings: Settings,
+    offset: usize,
+    failed: bool,
+    output: PhantomData<(&'de (), T)>,
 }
 
 impl<'de, B: AsRef<[u8]>, T: Deserialize<'de>> StreamDeserializer<'de, B, T> {
@@ -24,23 +25,40 @@ impl<'de, B: AsRef<[u8]>, T: Deserialize<'de>> StreamDeserializer<'de, B, T> {
 
     /// Create a JSON stream deserializer, with settings
     pub fn new_with_settings(buffer: B, settings: Settings) -> Self {
-        let shared = Arc::new(Shared::new(&settings));
-        let _shared_stack = SharedStack::push(shared.clone());
         Self {
-            base:   BaseStreamDeserializer::new(IoRead::new(Reader::new(buffer, shared.clone()))),
-            shared,
+            buffer,
+            settings,
+            offset: 0,
+            failed: false,
+            output: PhantomData,
         }
     }
 
     /// Returns the number of bytes so far deserialized into a successful `T`.
-    pub fn byte_offset(&self) -> usize { self.base.byte_offset() }
+    pub fn byte_offset(&self) -> usize { self.offset }
 }
 
 impl<'de, B: AsRef<[u8]>, T: Deserialize<'de>> Iterator for StreamDeserializer<'de, B, T> {
     type Item = Result<T>;
     fn next(&mut self) -> Option<Result<T>> {
-        let _shared_stack = SharedStack::push(self.shared.clone());
-        self.base.next()
+        if self.failed { return None }
+
+        // `B` doesn't outlive `'de`, so nothing may be borrowed from it.
+        let mut de = Deserializer::new(NoBorrow(SliceRead::new_at(self.buffer.as_ref(), self.offset)), &self.settings);
+
+        let result = de.deserialize_next();
+
+        match result {
+            Ok(Some(value)) => {
+                self.offset = de.offset();
+                Some(Ok(value))
+            },
+            Ok(None) => None,
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            },
+        }
     }
 }
 
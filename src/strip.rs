@@ -0,0 +1,57 @@
+/// Convert JSONC into strict JSON of the same length, by replacing `// line` and `/* block */` comments and trailing commas with spaces.
+///
+/// Newlines inside block comments are kept, so byte offsets - as well as lines and columns - reported by other JSON tools for the result also apply to `jsonc`.
+/// Only comments and trailing commas are replaced: `jsonc` is otherwise left as-is, without being validated.
+/// Like JSON, JSONC only has `"double quoted"` strings - an `'` is just another character.
+///
+/// ```
+/// # use json_spanned_value as jsv;
+/// let jsonc = "{\n  \"a\": 1, // one\n  \"b\": [2, /* two */],\n}";
+/// let json = jsv::strip_jsonc(jsonc);
+/// assert_eq!(json, "{\n  \"a\": 1,       \n  \"b\": [2           ] \n}");
+///
+/// let value = jsv::from_str::<jsv::spanned::Value>(&json).unwrap();
+/// let two = value.pointer("/b/0").unwrap();
+/// assert_eq!(&jsonc[two.range()], "2");
+/// ```
+pub fn strip_jsonc(jsonc: &str) -> String {
+    let bytes = jsonc.as_bytes();
+    let mut out = bytes.to_vec();
+    let blank = |out: &mut Vec<u8>, start: usize, end: usize| for b in &mut out[start .. end] { if *b != b'\n' && *b != b'\r' { *b = b' '; } };
+
+    let mut comma = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                let end = bytes[i ..].iter().position(|&b| b == b'\n' || b == b'\r').map_or(bytes.len(), |n| i + n);
+                blank(&mut out, i, end);
+                i = end;
+                continue;
+            },
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let end = bytes[i + 2 ..].windows(2).position(|w| w == b"*/").map_or(bytes.len(), |n| i + 2 + n + 2);
+                blank(&mut out, i, end);
+                i = end;
+                continue;
+            },
+            b'"' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                comma = None;
+            },
+            b' ' | b'\t' | b'\n' | b'\r' => {},
+            b',' => comma = Some(i),
+            close => {
+                if let (Some(comma), true) = (comma, close == b']' || close == b'}') { out[comma] = b' '; }
+                comma = None;
+            },
+        }
+        i += 1;
+    }
+
+    // Every byte of a comment is replaced, so multi-byte characters become several spaces rather than invalid UTF-8
+    String::from_utf8(out).expect("strip_jsonc: replaced part of a multi-byte character")
+}
@@ -2,6 +2,7 @@ use super::*;
 use std::collections::BTreeMap;
 use serde::Deserialize;
 
+#[allow(clippy::type_complexity)]
 fn do_test_obj(json: &str, expected: Vec<(&str, &str, fn(&Value) -> bool)>) {
     let expected = expected.into_iter().map(|(k, v, v2)| (k, (v, v2))).collect::<BTreeMap<_, _>>();
     let parsed : Value = super::from_str(json).unwrap();
@@ -48,14 +49,24 @@ fn do_test_obj(json: &str, expected: Vec<(&str, &str, fn(&Value) -> bool)>) {
 
 
 
+#[allow(deprecated)]
 #[test] fn duplicate_keys_allow() {
     let json = "{\"a\": 1, \"a\": 2}";
     let settings = Settings { allow_duplicate_keys: true, ..Settings::default() };
     let o : spanned::Object = from_str_with_settings(json, &settings).unwrap();
     let a = o.get("a").unwrap().as_number().unwrap().as_u64().unwrap();
     assert!(a == 1 || a == 2);
+
+    // Typed structs keep the last value too, instead of rejecting the repeated field
+    let point : Point = from_str_with_settings("{\"x\": 1, \"y\": 2, \"x\": 3}", &settings).unwrap();
+    assert_eq!((*point.x, point.y), (3, 2));
+
+    // An explicit duplicate_keys policy wins
+    let settings = Settings { allow_duplicate_keys: true, duplicate_keys: Some(DuplicateKeys::Error), ..Settings::default() };
+    assert!(from_str_with_settings::<Point>("{\"x\": 1, \"y\": 2, \"x\": 3}", &settings).unwrap_err().duplicate_of().is_some());
 }
 
+#[allow(deprecated)]
 #[test] fn duplicate_keys_deny() {
     let json = "{\"a\": 1, \"a\": 2}";
     let settings = Settings { allow_duplicate_keys: false, ..Settings::default() };
@@ -66,6 +77,126 @@ fn do_test_obj(json: &str, expected: Vec<(&str, &str, fn(&Value) -> bool)>) {
 
 
 
+#[derive(Debug, Deserialize)] struct Point { x: Spanned<u32>, y: u32 }
+#[derive(Debug, Deserialize)] enum Shape { Point { x: u32, y: u32 } }
+
+fn duplicate_keys(duplicate_keys: DuplicateKeys) -> Settings { Settings { duplicate_keys: Some(duplicate_keys), ..Settings::default() } }
+
+#[test] fn duplicate_keys_default() {
+    // Only Maps and Values are checked, like serde_json
+    let json = "{\"x\": 1, \"y\": 2, \"x\": 3}";
+    for err in [
+        from_str::<spanned::Value>(json).unwrap_err(),
+        from_reader::<_, Map<String, u32>>(Trickle(json.as_bytes())).unwrap_err(),
+    ].iter() {
+        assert_eq!(err.to_string(), "duplicate key `x` at line 1 column 21");
+        assert_eq!((err.range(), err.duplicate_of()), (Some(17 .. 20), Some(1 .. 4)));
+    }
+    assert_eq!(from_str::<std::collections::HashMap<String, u32>>(json).unwrap()["x"], 3);
+    assert_eq!(from_str::<serde_json::Value>(json).unwrap()["x"], 3);
+    assert!(from_str::<Point>(json).unwrap_err().to_string().starts_with("duplicate field `x`"));
+}
+
+#[test] fn duplicate_keys_error() {
+    let settings = duplicate_keys(DuplicateKeys::Error);
+    let json = "{\"x\": 1, \"y\": 2, \"x\": 3}";
+    for err in [
+        from_str_with_settings::<Point>(json, &settings).unwrap_err(),
+        from_str_with_settings::<spanned::Value>(json, &settings).unwrap_err(),
+        from_str_with_settings::<serde_json::Value>(json, &settings).unwrap_err(),
+        from_str_with_settings::<std::collections::HashMap<String, u32>>(json, &settings).unwrap_err(),
+        from_reader_with_settings::<_, Map<String, u32>>(Trickle(json.as_bytes()), &settings).unwrap_err(),
+    ].iter() {
+        assert_eq!(err.to_string(), "duplicate key `x` at line 1 column 21");
+        assert!(err.is_data());
+        assert_eq!(err.range(), Some(17 .. 20));
+        assert_eq!(err.duplicate_of(), Some(1 .. 4));
+    }
+
+    let json = "{\"Point\": {\"x\": 1, \"x\": 2}}";
+    let err = from_str_with_settings::<Shape>(json, &settings).unwrap_err();
+    assert_eq!((err.range(), err.duplicate_of(), err.pointer()), (Some(19 .. 22), Some(11 .. 14), "/Point"));
+
+    let err = from_str::<spanned::Value>("{\"a\": [{\"b\": 1, \"b\": 2}]}").unwrap_err();
+    assert_eq!(err.pointer(), "/a/0");
+
+    // Objects with too many keys to search linearly
+    let keys = (0 .. 20).map(|i| format!("\"k{}\": {}", i, i)).collect::<Vec<_>>().join(", ");
+    from_str::<spanned::Value>(&format!("{{{}}}", keys)).unwrap();
+    let json = format!("{{{}, \"k3\": 3}}", keys);
+    let err = from_str::<spanned::Value>(&json).unwrap_err();
+    assert_eq!((&json[err.range().unwrap()], &json[err.duplicate_of().unwrap()]), ("\"k3\"", "\"k3\""));
+    assert_eq!(err.duplicate_of(), Some(28 .. 32));
+
+    // Only keys that are actually used matter
+    from_str_with_settings::<Point>("{\"x\": 1, \"y\": 2, \"z\": {\"w\": 1, \"w\": 2}}", &settings).unwrap();
+    from_str_with_settings::<spanned::Value>("{\"a\": {\"b\": 1}, \"c\": {\"b\": 2}}", &settings).unwrap();
+}
+
+#[test] fn duplicate_keys_keep_first() {
+    let settings = duplicate_keys(DuplicateKeys::KeepFirst);
+    let json = "{\"x\": 1, \"y\": 2, \"x\": 3}";
+    let point : Point = from_str_with_settings(json, &settings).unwrap();
+    assert_eq!((*point.x, point.x.range(), point.y), (1, 6 .. 7, 2));
+    assert_eq!(from_str_with_settings::<spanned::Value>(json, &settings).unwrap().pointer("/x").unwrap().range(), 6 .. 7);
+    assert_eq!(from_str_with_settings::<serde_json::Value>(json, &settings).unwrap()["x"], 1);
+    assert_eq!(from_reader_with_settings::<_, std::collections::HashMap<String, u32>>(Trickle(json.as_bytes()), &settings).unwrap()["x"], 1);
+    assert!(matches!(from_str_with_settings(&format!("{{\"Point\": {}}}", json), &settings).unwrap(), Shape::Point { x: 1, y: 2 }));
+
+    // Later duplicates are skipped without being deserialized
+    let point : Point = from_str_with_settings("{\"x\": 1, \"y\": 2, \"x\": \"three\", \"y\": [4]}", &settings).unwrap();
+    assert_eq!((*point.x, point.y), (1, 2));
+}
+
+#[allow(deprecated)]
+#[test] fn duplicate_keys_keep_last() {
+    let json = "{\"x\": 1, \"y\": 2, \"x\": 3}";
+    for settings in [duplicate_keys(DuplicateKeys::KeepLast), Settings { allow_duplicate_keys: true, ..Settings::default() }].iter() {
+        let point : Point = from_str_with_settings(json, settings).unwrap();
+        assert_eq!((*point.x, point.x.range(), point.y), (3, 22 .. 23, 2));
+        assert_eq!(from_str_with_settings::<spanned::Value>(json, settings).unwrap().pointer("/x").unwrap().range(), 22 .. 23);
+        assert_eq!(from_str_with_settings::<serde_json::Value>(json, settings).unwrap()["x"], 3);
+        assert!(matches!(from_str_with_settings(&format!("{{\"Point\": {}}}", json), settings).unwrap(), Shape::Point { x: 3, y: 2 }));
+
+        // Earlier duplicates are skipped without being deserialized
+        let point : Point = from_str_with_settings("{\"x\": \"one\", \"y\": [2], \"x\": 3, \"y\": 4}", settings).unwrap();
+        assert_eq!((*point.x, point.y), (3, 4));
+    }
+
+    // Structs nested in a struct are scanned along with it
+    #[derive(Deserialize)] struct Line { a: Point, b: Vec<Point>, c: std::collections::HashMap<String, Point> }
+    let settings = duplicate_keys(DuplicateKeys::KeepLast);
+    let json = "{\"a\": {\"x\": 1, \"y\": 2, \"x\": 3}, \"b\": [{\"x\": 4, \"y\": 5}, {\"y\": 6, \"x\": 7, \"y\": 8}], \"c\": {\"d\": {\"x\": 9, \"x\": 10, \"y\": 11}}, \"a\": {\"x\": 12, \"y\": 13}}";
+    let line : Line = from_str_with_settings(json, &settings).unwrap();
+    assert_eq!((*line.a.x, line.a.y), (12, 13));
+    assert_eq!(line.b.iter().map(|p| (*p.x, p.y)).collect::<Vec<_>>(), vec![(4, 5), (7, 8)]);
+    assert_eq!((*line.c["d"].x, line.c["d"].y), (10, 11));
+
+    // Looking ahead for duplicates must keep the whole object buffered
+    let json = format!("[{{\"x\": 1, \"y\": [{}0], \"x\": 3, \"y\": 4}}]", "0, ".repeat(10_000));
+    let points : Vec<Point> = from_reader_with_settings(json.as_bytes(), &settings).unwrap();
+    assert_eq!(&json[points[0].x.range()], "3");
+    let points : Vec<Point> = from_reader_with_settings(Trickle(json.as_bytes()), &settings).unwrap();
+    assert_eq!((*points[0].x, points[0].y), (3, 4));
+}
+
+#[test] fn duplicate_keys_collect() {
+    let json = "[{\"x\": 1, \"y\": 2, \"x\": 3, \"x\": 4}]";
+    let mut de = Deserializer::from_str(json, &duplicate_keys(DuplicateKeys::Collect));
+    let points = Vec::<Point>::deserialize(&mut de).unwrap();
+    de.end().unwrap();
+    assert_eq!(*points[0].x, 4);
+
+    let duplicates = de.take_duplicate_keys();
+    assert_eq!(duplicates.iter().map(|d| (d.key().range(), d.first(), d.pointer())).collect::<Vec<_>>(), vec![
+        (18 .. 21, 2 .. 5, "/0/x"),
+        (26 .. 29, 2 .. 5, "/0/x"),
+    ]);
+    assert!(de.take_duplicate_keys().is_empty());
+}
+
+
+
 #[test] fn trailing_object_commas_allow() {
     let json = "{\"a\": 1, \"b\": 2, }";
     let settings = Settings { allow_trailing_comma: true, ..Settings::default() };
@@ -191,16 +322,34 @@ fn do_test_obj(json: &str, expected: Vec<(&str, &str, fn(&Value) -> bool)>) {
 
 
 
+#[test] fn numbers() {
+    let json = "[0, -0, 18446744073709551615, 18446744073709551616, -9223372036854775808, -9223372036854775809, 1.5e3, 10000000000000000000000]";
+    let a : Vec<serde_json::Value> = from_str(json).unwrap();
+    let expected : Vec<serde_json::Value> = serde_json::from_str(json).unwrap();
+    assert_eq!(a, expected);
+    assert_eq!(a[1].as_f64().map(f64::is_sign_negative), Some(true));
+
+    let a : spanned::Array = from_str(json).unwrap();
+    assert_eq!(&json[a[3].range()], "18446744073709551616");
+    assert_eq!(&json[a[7].range()], "10000000000000000000000");
+}
+
+#[allow(clippy::useless_conversion)]
 #[test] fn stream_numbers() {
-    let json = "1 2 3 "; // FIXME: last span slightly off without trailing space (4..4 instead of 4..5)
+    let json = "1 2 3 ";
     for (expected, actual) in [1,2,3].iter().copied().zip(StreamDeserializer::<&str, spanned::Number>::new(json).into_iter()) {
         let actual = actual.unwrap();
         let actual_str = &json[actual.range()];
         assert_eq!(expected.to_string(), actual_str);
         assert_eq!(expected.to_string(), actual.into_inner().to_string());
     }
+
+    let json = "1 2 3";
+    let last = StreamDeserializer::<&str, spanned::Number>::new(json).last().unwrap().unwrap();
+    assert_eq!(last.range(), 4..5);
 }
 
+#[allow(clippy::useless_conversion)]
 #[test] fn stream_arrays() {
     let json = "[1] [2] [3]";
     for (expected, actual) in ["[1]","[2]","[3]"].iter().copied().zip(StreamDeserializer::<&str, spanned::Array>::new(json).into_iter()) {
@@ -211,6 +360,7 @@ fn do_test_obj(json: &str, expected: Vec<(&str, &str, fn(&Value) -> bool)>) {
     }
 }
 
+#[allow(clippy::useless_conversion)]
 #[test] fn stream_objects() {
     let json = "{\"a\":1} {\"b\":2} {\"c\":3}";
     for ((expected_obj, expected_key, expected_val), actual_obj) in [("{\"a\":1}", "a", 1), ("{\"b\":2}", "b", 2), ("{\"c\":3}", "c", 3)].iter().copied().zip(StreamDeserializer::<&str, spanned::Object>::new(json).into_iter()) {
@@ -226,3 +376,928 @@ fn do_test_obj(json: &str, expected: Vec<(&str, &str, fn(&Value) -> bool)>) {
         }
     }
 }
+
+
+
+#[test] fn borrowed_str() {
+    let json = "{\"a\": \"borrowed\"}";
+    let o : std::collections::BTreeMap<&str, Spanned<&str>> = from_str(json).unwrap();
+    assert_eq!(*o["a"].get_ref(), "borrowed");
+    assert_eq!(&json[o["a"].range()], "\"borrowed\"");
+    assert_eq!(o["a"].get_ref().as_ptr(), json[o["a"].start()+1..].as_ptr(), "string wasn't borrowed from input");
+
+    let json = "{\"b\": \"escaped\\n\"}";
+    let err = from_str::<std::collections::BTreeMap<&str, &str>>(json).unwrap_err();
+    assert!(err.to_string().contains("borrowed string"), "err: {}", err);
+}
+
+#[test] fn borrowed_struct() {
+    use std::borrow::Cow;
+
+    #[derive(Deserialize)] struct Borrowing<'a> {
+        #[serde(borrow)] plain:     Cow<'a, str>,
+        #[serde(borrow)] escaped:   Cow<'a, str>,
+        #[serde(borrow)] spanned:   Spanned<&'a str>,
+    }
+
+    let json = "{\"plain\": \"abc\", \"escaped\": \"a\\tb\", \"spanned\": \"def\"}";
+    let parsed : Borrowing = from_str(json).unwrap();
+    assert!(matches!(parsed.plain, Cow::Borrowed("abc")));
+    assert!(matches!(parsed.escaped, Cow::Owned(ref s) if s == "a\tb"));
+    assert_eq!(&json[parsed.spanned.range()], "\"def\"");
+}
+
+
+
+/// Trickles out a single byte per [std::io::Read::read] call, to exercise lookahead buffering.
+struct Trickle<'a>(&'a [u8]);
+impl std::io::Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() || self.0.is_empty() { return Ok(0) }
+        buf[0] = self.0[0];
+        self.0 = &self.0[1..];
+        Ok(1)
+    }
+}
+
+#[test] fn reader_matches_str() {
+    let json = "{\"a\": 1, /* comment */ \"b\": [true, null, \"s\\u00e9\"], // comment\n \"c\": {\"d\": -1.5e3},}";
+    let settings = Settings { allow_comments: true, allow_trailing_comma: true, ..Settings::default() };
+    let expected : spanned::Value = from_str_with_settings(json, &settings).unwrap();
+    for actual in [
+        from_reader_with_settings::<_, spanned::Value>(json.as_bytes(), &settings).unwrap(),
+        from_reader_with_settings::<_, spanned::Value>(Trickle(json.as_bytes()), &settings).unwrap(),
+    ].iter() {
+        for path in ["", "/a", "/b", "/b/0", "/b/1", "/b/2", "/c", "/c/d"].iter() {
+            assert_eq!(expected.pointer(path).unwrap().range(), actual.pointer(path).unwrap().range(), "path: {:?}", path);
+        }
+        assert_eq!("s\u{e9}", actual.pointer("/b/2").unwrap().as_string().unwrap());
+    }
+}
+
+#[test] fn reader_large() {
+    let mut json = String::from("[");
+    for i in 0 .. 10_000 { json.push_str(&format!("\n  {{\"key\": \"value {}\"}},", i)); }
+    json.push_str("\n  \"last\"\n]");
+
+    let a : spanned::Array = from_reader(Trickle(json.as_bytes())).unwrap();
+    assert_eq!(a.len(), 10_001);
+    assert_eq!(&json[a[9_999].range()], "{\"key\": \"value 9999\"}");
+    assert_eq!(&json[a[10_000].range()], "\"last\"");
+
+    let json = json.replace("\"last\"", "\"last\",");
+    let err = from_reader::<_, spanned::Array>(Trickle(json.as_bytes())).unwrap_err();
+    assert_eq!((err.line(), err.column()), (10_003, 1), "err: {}", err);
+    assert_eq!(err.offset_within(&json), Some(json.len()-1));
+}
+
+#[test] fn reader_io_error() {
+    struct Broken;
+    impl std::io::Read for Broken {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> { Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "broken pipe")) }
+    }
+    let err = from_reader::<_, spanned::Value>(Broken).unwrap_err();
+    assert!(err.to_string().contains("broken pipe"), "err: {}", err);
+    assert!(err.is_io());
+    assert!(std::error::Error::source(&err).is_some());
+    assert!(serde_json::Error::from(err).is_io());
+}
+
+#[test] fn path() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/demo.json");
+    let text = std::fs::read_to_string(path).unwrap();
+    let expected : spanned::Object = from_str(&text).unwrap();
+    let actual : spanned::Object = from_path(path).unwrap();
+    for ((ek, ev), (ak, av)) in expected.iter().zip(actual.iter()) {
+        assert_eq!(ek.range(), ak.range());
+        assert_eq!(ev.range(), av.range());
+    }
+
+    assert!(from_path::<_, spanned::Value>(concat!(env!("CARGO_MANIFEST_DIR"), "/examples/does-not-exist.json")).is_err());
+}
+
+#[test] fn deserializer_across_threads() {
+    let json = "{\"a\": [1, 2], \"b\": \"c\"}";
+    let mut de = Deserializer::from_reader(std::io::Cursor::new(json.as_bytes().to_vec()), &Settings::default());
+    let v = std::thread::spawn(move || {
+        let v = <spanned::Value as serde::Deserialize>::deserialize(&mut de).unwrap();
+        de.end().unwrap();
+        v
+    }).join().unwrap();
+    assert_eq!(v.range(), 0 .. json.len());
+    assert_eq!(&json[v.pointer("/a/1").unwrap().range()], "2");
+    assert_eq!(&json[v.pointer("/b").unwrap().range()], "\"c\"");
+}
+
+#[test] fn foreign_deserializer() {
+    let v : spanned::Value = serde_json::from_str("{\"a\": [1, 2], \"b\": \"c\"}").unwrap();
+    assert_eq!(v.range(), 0 .. 0);
+    assert_eq!(v.pointer("/a/1").unwrap().as_number().and_then(|n| n.as_u64()), Some(2));
+    assert_eq!(v.pointer("/b").unwrap().as_string(), Some("c"));
+}
+
+#[test] fn missing_spanned_fields() {
+    #[derive(Deserialize)] struct Inner { #[allow(dead_code)] y: u32 }
+    #[derive(Deserialize)] struct A { x: Spanned<Option<u32>>, #[serde(default)] n: Spanned<u32>, inner: Spanned<Option<Inner>> }
+
+    for a in [from_str::<A>("{}").unwrap(), serde_json::from_str::<A>("{}").unwrap()].iter() {
+        assert_eq!((*a.x, a.x.range()), (None, 0 .. 0));
+        assert_eq!((*a.n, a.n.range()), (0, 0 .. 0));
+        assert!(a.inner.is_none());
+    }
+
+    let json = "{\"x\": 1, \"n\": 2}";
+    let a = from_str::<A>(json).unwrap();
+    assert_eq!((*a.x, &json[a.x.range()]), (Some(1), "1"));
+    assert_eq!((*a.n, &json[a.n.range()]), (2, "2"));
+
+    // Missing fields within a present value are still errors
+    assert_eq!(from_str::<A>("{\"inner\": {}}").err().unwrap().to_string(), "missing field `y` at line 1 column 12");
+    assert!(from_str::<Spanned<Option<u32>>>("").is_err());
+    let settings = Settings { allow_comments: true, ..Settings::default() };
+    assert!(from_str_with_settings::<A>("{\"x\": /* unterminated", &settings).is_err());
+
+    // Missing fields are recognized whatever the error type says
+    #[derive(Debug)] struct Reworded(String);
+    impl std::fmt::Display for Reworded { fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result { write!(fmt, "oops: {}", self.0) } }
+    impl std::error::Error for Reworded {}
+    impl serde::de::Error for Reworded { fn custom<T: std::fmt::Display>(msg: T) -> Self { Reworded(msg.to_string()) } }
+    #[derive(Deserialize)] struct B { x: Spanned<Option<u32>>, y: Spanned<u32> }
+    let b = B::deserialize(serde::de::value::MapDeserializer::<_, Reworded>::new(vec![("y", 2u32)].into_iter())).unwrap();
+    assert_eq!((*b.x, *b.y), (None, 2));
+}
+
+#[test] fn json5() {
+    let json = "\u{FEFF}// comments\n{\n  unquoted: 'and you can quote me on that',\n  singleQuotes: 'I can use \"double quotes\" here',\n  lineBreaks: \"Look, Mom! \\\nNo \\\\n's!\",\n  hexadecimal: 0xdecaf,\n  leadingDecimalPoint: .8675309, andTrailing: 8675309.,\n  positiveSign: +1,\n  negativeHex: -0x10,\n  trailingComma: 'in objects', andIn: ['arrays',],\n  \"backwardsCompatible\": \"with JSON\",\n\u{A0}$_ünïcode\u{2003}: 'escapes \\x41\\v\\0\\'\\q',\n}\n";
+    let settings = Settings { allow_json5: true, ..Settings::default() };
+    let o : spanned::Object = from_str_with_settings(json, &settings).unwrap();
+    let plain : serde_json::Value = from_str_with_settings(json, &settings).unwrap();
+
+    let expect = |key: &str, text: &str, value: serde_json::Value| {
+        let (k, v) = o.get_key_value(key).unwrap_or_else(|| panic!("missing key {:?}", key));
+        assert_eq!(&json[k.range()], key.trim_matches('"'), "key {:?}", key);
+        assert_eq!(&json[v.range()], text, "key {:?}", key);
+        assert_eq!(plain[key], value, "key {:?}", key);
+    };
+    expect("unquoted",              "'and you can quote me on that'",       "and you can quote me on that".into());
+    expect("singleQuotes",          "'I can use \"double quotes\" here'",   "I can use \"double quotes\" here".into());
+    expect("lineBreaks",            "\"Look, Mom! \\\nNo \\\\n's!\"",       "Look, Mom! No \\n's!".into());
+    expect("hexadecimal",           "0xdecaf",                              0xdecaf.into());
+    expect("leadingDecimalPoint",   ".8675309",                             0.8675309.into());
+    expect("andTrailing",           "8675309.",                             8675309.0.into());
+    expect("positiveSign",          "+1",                                   1.into());
+    expect("negativeHex",           "-0x10",                                (-16).into());
+    expect("trailingComma",         "'in objects'",                         "in objects".into());
+    expect("andIn",                 "['arrays',]",                          serde_json::json!(["arrays"]));
+    expect("$_ünïcode",             "'escapes \\x41\\v\\0\\'\\q'",          "escapes A\u{b}\0'q".into());
+    assert_eq!(&json[o.get_key_value("backwardsCompatible").unwrap().0.range()], "\"backwardsCompatible\"");
+
+    let from_reader : spanned::Object = from_reader_with_settings(Trickle(json.as_bytes()), &settings).unwrap();
+    for ((ek, ev), (ak, av)) in o.iter().zip(from_reader.iter()) {
+        assert_eq!((ek.get_ref(), ek.range(), ev.range()), (ak.get_ref(), ak.range(), av.range()));
+    }
+
+    assert!(from_str::<spanned::Value>("{unquoted: 1}").is_err());
+    assert!(from_str::<spanned::Value>("'single'").is_err());
+    assert!(from_str::<spanned::Value>("0x10").is_err());
+    assert!(from_str::<spanned::Value>("+1").is_err());
+}
+
+#[test] fn json5_numbers() {
+    let settings = Settings { allow_json5: true, ..Settings::default() };
+    let n : Vec<f64> = from_str_with_settings("[Infinity, -Infinity, +Infinity, NaN, -NaN, 0x0, -.5e1, 5.e-1]", &settings).unwrap();
+    assert_eq!(&n[..3], &[f64::INFINITY, f64::NEG_INFINITY, f64::INFINITY]);
+    assert!(n[3].is_nan() && n[4].is_nan());
+    assert_eq!(&n[5..], &[0.0, -5.0, 0.5]);
+
+    let json = "{x: NaN, y: -Infinity}";
+    let v : spanned::Value = from_str_with_settings(json, &settings).unwrap();
+    assert!(v.pointer("/x").unwrap().is_null());
+    assert_eq!(&json[v.pointer("/y").unwrap().range()], "-Infinity");
+    assert!(super::from_str_partial(json, &settings).0.pointer("/x").unwrap().is_null());
+    assert!(from_str_with_settings::<serde_json::Value>(json, &settings).unwrap()["y"].is_null());
+
+    #[derive(Deserialize)] struct Typed { port: Spanned<u16>, #[serde(rename = "host-name")] host: Spanned<String> }
+    let json = "{port: 0x1F90, 'host-name': 'localhost'}";
+    let t : Typed = from_str_with_settings(json, &settings).unwrap();
+    assert_eq!((*t.port.get_ref(), &json[t.port.range()]), (8080, "0x1F90"));
+    assert_eq!(&json[t.host.range()], "'localhost'");
+
+    for bad in ["0x", "01", ".", "1.e", "0xFFFFFFFFFFFFFFFFF", "Inf", "'\\1'", "'\\01'", "'line\nbreak'", "{a\\u0062: 1}"].iter() {
+        assert!(from_str_with_settings::<spanned::Value>(bad, &settings).is_err(), "should've failed: {:?}", bad);
+    }
+    let err = from_str_with_settings::<String>("'\\01'", &settings).unwrap_err();
+    assert_eq!((err.to_string(), err.range()), ("invalid escape at line 1 column 3".into(), Some(2 .. 3)));
+}
+
+#[test] fn error_details() {
+    #[derive(Debug, Deserialize)] struct Server { #[allow(dead_code)] port: u16 }
+    #[derive(Debug, Deserialize)] struct Config { #[allow(dead_code)] servers: Vec<Server> }
+
+    let json = "{\"servers\": [{\"port\": 80}, {\"port\": \"eighty\"}]}";
+    let err = from_str::<Config>(json).unwrap_err();
+    assert!(err.is_data(), "err: {:?}", err);
+    assert_eq!(err.pointer(), "/servers/1/port");
+    assert_eq!(&json[err.range().unwrap()], "\"eighty\"");
+    assert_eq!(err.offset(), Some(json.find("\"eighty\"").unwrap() + 8));
+
+    let json = "{\"a/b\": {\"~\": [true, nul]}}";
+    let err = from_str::<spanned::Value>(json).unwrap_err();
+    assert!(err.is_syntax(), "err: {:?}", err);
+    assert_eq!(err.pointer(), "/a~1b/~0/1");
+    assert_eq!(&json[err.range().unwrap()], "]");
+
+    let json = "[1, 2";
+    let err = from_str::<spanned::Value>(json).unwrap_err();
+    assert!(err.is_eof(), "err: {:?}", err);
+    assert_eq!(err.range(), Some(json.len() .. json.len()));
+
+    let sj = serde_json::Error::from(err);
+    assert_eq!((sj.line(), sj.column()), (1, 6));
+    assert_eq!(sj.to_string(), "EOF while parsing a list at line 1 column 6");
+    let err = Error::from(sj);
+    assert_eq!((err.line(), err.column(), err.range()), (1, 6, None));
+    assert_eq!(err.to_string(), "EOF while parsing a list at line 1 column 6");
+
+    let err = from_reader::<_, spanned::Value>(Trickle(b"[1, 2")).unwrap_err();
+    assert_eq!(err.range(), Some(5 .. 5));
+}
+
+#[test] fn line_index() {
+    let json = "{\r\n  \"a\": 1,\r\n  \"b\": tru\r\n}\n";
+    let index = LineIndex::new(json);
+    assert_eq!(index.line_count(), 5);
+    assert_eq!(index.line_col(0), Some((1, 1)));
+    assert_eq!(index.line_col(1), Some((1, 2))); // '\r'
+    assert_eq!(index.line_col(3), Some((2, 1)));
+    assert_eq!(index.line_col(json.len()), Some((5, 1)));
+    assert_eq!(index.line_col(json.len() + 1), None);
+    for offset in 0 ..= json.len() {
+        let (line, col) = index.line_col(offset).unwrap();
+        assert_eq!(index.offset(line, col), Some(offset));
+    }
+    assert_eq!(index.offset(0, 1), None);
+    assert_eq!(index.offset(1, 0), None);
+    assert_eq!(index.offset(1, 4), None);
+    assert_eq!(index.offset(6, 1), None);
+    assert_eq!(&json[index.line_range(2).unwrap()], "  \"a\": 1,");
+    assert_eq!(&json[index.line_range(4).unwrap()], "}");
+    assert_eq!(index.line_range(5), Some(json.len() .. json.len()));
+
+    let err = from_str::<spanned::Value>(json).unwrap_err();
+    let sj = serde_json::from_str::<serde_json::Value>(json).unwrap_err();
+    assert_eq!(err.offset_within_index(&index), err.offset_within(json));
+    assert_eq!(sj.offset_within_index(&index), sj.offset_within(json));
+    assert_eq!(index.line_col(err.offset().unwrap()), Some((err.line(), err.column())));
+
+    // Implementations outside this crate need not know about LineIndex
+    struct Foreign(Option<(usize, usize)>);
+    impl ErrorExt for Foreign {
+        fn offset_within(&self, text: &str) -> Option<usize> { self.0.and_then(|(line, column)| LineIndex::new(text).offset(line, column)) }
+        fn line_column(&self) -> Option<(usize, usize)> { self.0 }
+    }
+    assert_eq!(Foreign(Some((2, 3))).offset_within_index(&index), Some(5));
+    assert_eq!(Foreign(None).offset_within_index(&index), None);
+    assert_eq!(Foreign(Some((2, 3))).offset_within(json), Some(5));
+}
+
+#[test] fn line_index_units() {
+    let json = "{\r\n  \"猫\": [\"😀\", 2],\r\n  \"é\": {\"x\": null}\r\n}";
+    let index = LineIndex::new(json);
+    for unit in [ColumnUnit::Byte, ColumnUnit::Utf16, ColumnUnit::Char].iter().copied() {
+        for offset in (0 ..= json.len()).filter(|o| json.is_char_boundary(*o)) {
+            let (line, col) = index.line_col_in(offset, unit).unwrap();
+            assert_eq!(index.offset_in(line, col, unit), Some(offset), "{:?} {}", unit, offset);
+        }
+    }
+
+    let emoji = json.find('😀').unwrap();
+    assert_eq!(index.line_col_in(emoji + 1, ColumnUnit::Utf16), None);
+    assert_eq!(index.line_col_in(emoji,     ColumnUnit::Byte),  Some((2, 12)));
+    assert_eq!(index.line_col_in(emoji,     ColumnUnit::Utf16), Some((2, 10)));
+    assert_eq!(index.line_col_in(emoji,     ColumnUnit::Char),  Some((2, 10)));
+    assert_eq!(index.line_col_in(emoji + 4, ColumnUnit::Utf16), Some((2, 12)));
+    assert_eq!(index.line_col_in(emoji + 4, ColumnUnit::Char),  Some((2, 11)));
+    assert_eq!(index.offset_in(2, 11, ColumnUnit::Utf16), None); // between surrogates
+    assert_eq!(index.offset_in(2, 30, ColumnUnit::Utf16), None); // past end of line
+
+    let value : spanned::Value = from_str(json).unwrap();
+    let at = |line, col| value.value_at(index.offset_in(line, col, ColumnUnit::Utf16).unwrap()).map(|v| v.range());
+    let x = json.find("null").unwrap();
+    assert_eq!(at(3, 14), Some(x .. x + 4));                            // null
+    assert_eq!(at(3, 10), Some(x .. x + 4));                            // "x" key
+    assert_eq!(at(3, 8),  Some(json.find("{\"x").unwrap() .. x + 5));
+    assert_eq!(at(2, 4),  Some(json.find('[').unwrap() .. json.find(']').unwrap() + 1)); // "猫" key
+    assert_eq!(at(4, 1),  Some(0 .. json.len()));
+    assert!(value.value_at(json.len()).is_none());
+}
+
+#[test] fn serialize() {
+    let json = r#"{"b": [1, -2, 3.5, "s\"\n"], "a": {"z": null, "y": true}}"#;
+    let mut value : spanned::Value = from_str(json).unwrap();
+
+    #[cfg(not(feature = "preserve_order"))] let expected = r#"{"a":{"y":true,"z":null},"b":[1,-2,3.5,"s\"\n"]}"#;
+    #[cfg(    feature = "preserve_order" )] let expected = r#"{"b":[1,-2,3.5,"s\"\n"],"a":{"z":null,"y":true}}"#;
+    assert_eq!(to_string(&value).unwrap(), expected);
+    assert_eq!(serde_json::to_string(&value).unwrap(), expected);
+
+    value.get_mut().as_object_mut().unwrap().insert(Spanned::from(String::from("c")), Spanned::from(Value::Bool(false)));
+    let pretty = to_string_pretty(&value).unwrap();
+    assert!(pretty.contains("\n  \"c\": false"), "{}", pretty);
+    let reparsed : serde_json::Value = from_str(&pretty).unwrap();
+    assert_eq!(reparsed, serde_json::json!({"a": {"y": true, "z": null}, "b": [1, -2, 3.5, "s\"\n"], "c": false}));
+
+    let map : Spanned<Map<spanned::String, spanned::Number>> = from_str(r#"{"x": 1}"#).unwrap();
+    let mut out = Vec::new();
+    to_writer(&mut out, &map).unwrap();
+    assert_eq!(out, br#"{"x":1}"#);
+}
+
+#[test] fn serde_json_value_conversions() {
+    let json = r#"{"z": [1, -2.5, "s", null, true], "a": {"y": {}, "x": []}}"#;
+    let sj : serde_json::Value = serde_json::from_str(json).unwrap();
+    let value : spanned::Value = from_str(json).unwrap();
+
+    let back = spanned::Value::from(sj.clone());
+    assert_eq!(back.range(), 0 .. 0);
+    assert_eq!(to_string(&back).unwrap(), to_string(&value).unwrap()); // same key order
+
+    let converted = serde_json::Value::from(value);
+    assert_eq!(converted, sj);
+    assert_eq!(serde_json::to_string(&converted).unwrap(), serde_json::to_string(&sj).unwrap());
+    assert_eq!(serde_json::Value::from(back), sj);
+}
+
+#[test] fn from_value_spans() {
+    #[derive(Deserialize)] struct Server { host: spanned::String, port: Spanned<u16>, tags: Vec<spanned::String> }
+    #[derive(Deserialize)] enum Kind { Plain, Weighted { weight: Spanned<f64> } }
+    #[derive(Deserialize)] struct Upstream { kind: Kind, servers: BTreeMap<spanned::String, Server> }
+
+    let json = r#"{"type": "upstream", "config": {"kind": {"Weighted": {"weight": 0.5}}, "servers": {"a": {"host": "example.com", "port": 80, "tags": ["x"]}}}}"#;
+    let value : spanned::Value = from_str(json).unwrap();
+    assert_eq!(value.pointer("/type").unwrap().as_string(), Some("upstream"));
+
+    let config = value.pointer("/config").unwrap();
+    let borrowed = Upstream::deserialize(config).unwrap();
+    let (name, server) = borrowed.servers.iter().next().unwrap();
+    assert_eq!(&json[name.range()],         "\"a\"");
+    assert_eq!(&json[server.host.range()],  "\"example.com\"");
+    assert_eq!(&json[server.port.range()],  "80");
+    assert_eq!(&json[server.tags[0].range()], "\"x\"");
+    match borrowed.kind {
+        Kind::Weighted { weight } => assert_eq!(&json[weight.range()], "0.5"),
+        Kind::Plain => panic!("expected Kind::Weighted"),
+    }
+
+    #[derive(Deserialize)] struct Owned { kind: Kind, servers: BTreeMap<String, Spanned<serde_json::Value>> }
+    let value : spanned::Value = from_str(json).unwrap();
+    let owned : Owned = from_value(value.into_span_object().unwrap().into_inner().remove("config").unwrap()).unwrap();
+    assert!(matches!(owned.kind, Kind::Weighted { .. }));
+    assert_eq!(&json[owned.servers["a"].range()], r#"{"host": "example.com", "port": 80, "tags": ["x"]}"#);
+
+    let json = r#"{"kind": "Plain", "servers": {"a": {"host": "example.com", "port": "eighty", "tags": []}}}"#;
+    let value : spanned::Value = from_str(json).unwrap();
+    let err = Upstream::deserialize(&value).err().unwrap();
+    assert!(err.is_data(), "err: {:?}", err);
+    assert_eq!(err.pointer(), "/servers/a/port");
+    assert_eq!(&json[err.range().unwrap()], "\"eighty\"");
+
+    let json = r#"{"kind": "Plain", "servers": {"a": {"host": "example.com"}}}"#;
+    let err = from_value::<Upstream>(from_str(json).unwrap()).err().unwrap();
+    assert_eq!(err.pointer(), "/servers/a");
+    assert_eq!(&json[err.range().unwrap()], r#"{"host": "example.com"}"#);
+}
+
+#[test] fn adapters_and_seeds() {
+    #[derive(Debug, Deserialize)] struct Server { port: Spanned<u16> }
+    #[derive(Debug, Deserialize)] struct Config { servers: Vec<Server> }
+
+    let settings = Settings::default();
+    let json = r#"{"servers": [{"port": 80}, {"port": 8080, "extra": true}]}"#;
+    let mut de = Deserializer::from_str(json, &settings);
+    let mut ignored = Vec::new();
+    let config : Config = serde_ignored::deserialize(&mut de, |path| ignored.push(path.to_string())).unwrap();
+    de.end().unwrap();
+    assert_eq!(ignored, vec!["servers.1.extra"]);
+    assert_eq!(&json[config.servers[1].port.range()], "8080");
+
+    let json = r#"{"servers": [{"port": 80}, {"port": "eighty"}]}"#;
+    let mut de = Deserializer::from_str(json, &settings);
+    let err = serde_path_to_error::deserialize::<_, Config>(&mut de).unwrap_err();
+    assert!(err.path().to_string().starts_with("servers[1].port"), "path: {}", err.path());
+    assert_eq!(err.inner().pointer(), "/servers/1/port");
+    assert_eq!(&json[err.inner().range().unwrap()], "\"eighty\"");
+
+    struct Sum<'a>(&'a mut u64);
+    impl<'de> de::DeserializeSeed<'de> for Sum<'_> {
+        type Value = usize;
+        fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> std::result::Result<usize, D::Error> {
+            let values = Vec::<Spanned<u64>>::deserialize(deserializer)?;
+            *self.0 += values.iter().map(|v| **v).sum::<u64>();
+            Ok(values.last().map_or(0, |v| v.start()))
+        }
+    }
+    let mut total = 0;
+    assert_eq!(from_str_seed("[1, 2, 3]", Sum(&mut total)).unwrap(), 7);
+    assert_eq!(from_slice_seed(b"[4]", Sum(&mut total)).unwrap(), 1);
+    assert_eq!(total, 10);
+    assert!(from_str_seed("[1] 2", Sum(&mut total)).unwrap_err().is_syntax());
+    assert!(from_str_seed_with_settings("[1,]", &Settings { allow_trailing_comma: true, ..Settings::default() }, Sum(&mut total)).is_ok());
+}
+
+#[test] fn buffered_by_serde() {
+    #[derive(Deserialize)] struct Http { port: Spanned<u16> }
+    #[derive(Deserialize)] #[serde(tag = "type")] enum Tagged { #[serde(rename = "http")] Http(Http) }
+    #[derive(Deserialize)] struct Flat { #[serde(flatten)] http: Http }
+    #[derive(Deserialize)] #[serde(untagged)] enum Untagged { Http(Http) }
+
+    // serde's own buffering discards positions...
+    let json = r#"{"type": "http", "port": 8080}"#;
+    let Tagged::Http(http) = from_str::<Tagged>(json).unwrap();
+    assert_eq!(http.port.range(), 0 .. 0);
+    assert_eq!(*http.port, 8080);
+    assert_eq!(from_str::<Flat>(json).unwrap().http.port.range(), 0 .. 0);
+    let Untagged::Http(http) = from_str::<Untagged>(json).unwrap();
+    assert_eq!(http.port.range(), 0 .. 0);
+
+    // ...but buffering into a spanned::Value keeps them
+    let value : spanned::Value = from_str(json).unwrap();
+    assert_eq!(value.pointer("/type").unwrap().as_string(), Some("http"));
+    assert_eq!(&json[Http::deserialize(&value).unwrap().port.range()], "8080");
+}
+
+#[test] fn custom_span_errors() {
+    fn port<'de, D: de::Deserializer<'de>>(deserializer: D) -> std::result::Result<u16, D::Error> {
+        let port = Spanned::<u16>::deserialize(deserializer)?;
+        if *port < 1024 { return Err(port.error(format_args!("port {} out of range", *port))) }
+        Ok(port.into_inner())
+    }
+    #[derive(Debug, Deserialize)] struct Server { #[allow(dead_code)] #[serde(deserialize_with = "port")] port: u16 }
+    #[derive(Debug, Deserialize)] struct Config { #[allow(dead_code)] servers: Vec<Server> }
+
+    let json = "{\"servers\": [\n  {\"port\": 8080},\n  {\"port\": 80}\n]}";
+    let err = from_str::<Config>(json).unwrap_err();
+    assert!(err.is_data(), "err: {:?}", err);
+    assert_eq!(&json[err.range().unwrap()], "80");
+    assert_eq!(err.pointer(), "/servers/1/port");
+    assert_eq!(err.to_string(), "port 80 out of range at line 3 column 12");
+
+    let from_reader_err = from_reader::<_, Config>(json.as_bytes()).unwrap_err();
+    assert_eq!(from_reader_err.to_string(), err.to_string());
+    assert_eq!(from_reader_err.range(), err.range());
+
+    let value : spanned::Value = from_str(json).unwrap();
+    let err = Config::deserialize(&value).unwrap_err();
+    assert_eq!(&json[err.range().unwrap()], "80");
+    assert_eq!(err.pointer(), "/servers/1/port");
+
+    // Foreign error types get just the message, and don't leave a range behind for the next custom error
+    let err = serde_json::from_str::<Config>(json).unwrap_err();
+    assert_eq!(err.to_string(), "port 80 out of range at line 3 column 14");
+    let err = <Error as de::Error>::custom("unrelated");
+    assert_eq!(err.range(), None);
+    assert_eq!(<Error as de::Error>::custom(format_args!("{:>3}", 7)).to_string(), "  7");
+
+    let err : Error = error_at(3 .. 5, "explicit");
+    assert_eq!((err.range(), err.offset(), err.to_string()), (Some(3 .. 5), Some(3), String::from("explicit")));
+
+    // Messages containing `\0` are kept as-is, even if they look like a range
+    for msg in ["a\0b", "\x003..5\0c", "\0json_spanned_value::error_at x\0"].iter() {
+        let err = <Error as de::Error>::custom(msg);
+        assert_eq!((err.range(), err.to_string()), (None, String::from(*msg)));
+    }
+    let err : Error = error_at(3 .. 5, "\0nested\0");
+    assert_eq!((err.range(), err.to_string()), (Some(3 .. 5), String::from("\0nested\0")));
+
+    // Reformatting the message in between loses the range, but not the message
+    let err = <Error as de::Error>::custom(format_args!("{:>9}", error_at::<Error>(3 .. 5, "explicit")));
+    assert_eq!((err.range(), err.to_string()), (None, String::from("explicit")));
+}
+
+#[test] fn collect_errors() {
+    use std::collections::BTreeMap;
+
+    fn port<'de, D: de::Deserializer<'de>>(deserializer: D) -> std::result::Result<u16, D::Error> {
+        let port = Spanned::<u16>::deserialize(deserializer)?;
+        if *port < 1024 { return Err(port.error("port out of range")) }
+        Ok(port.into_inner())
+    }
+    #[derive(Debug, Deserialize, PartialEq)] enum Mode { Fast, Slow }
+    #[derive(Debug, Deserialize)] #[serde(deny_unknown_fields)] struct Server {
+        name:   spanned::String,
+        #[allow(dead_code)] #[serde(deserialize_with = "port")] port: u16,
+        mode:   Mode,
+        #[serde(default)] tags: Vec<String>,
+    }
+    #[derive(Debug, Deserialize)] struct Config {
+        servers:    Vec<Server>,
+        limits:     BTreeMap<u32, u32>,
+        backup:     Option<Server>,
+    }
+
+    let (config, errors) = from_str_collect_errors::<Config>("{\"servers\": [], \"limits\": {}}");
+    assert!(errors.is_empty(), "errors: {:?}", errors);
+    assert!(config.unwrap().backup.is_none());
+
+    let json = r#"{
+    "servers": [
+        {"name": "a", "port": 8080, "mode": "Medium"},
+        {"name": 2,   "port": 80,   "mode": "Slow"},
+        {"name": "c", "port": 8081, "mode": "Fast", "tags": [1, "ok"]},
+        {"name": "d", "prot": 8082, "mode": "Slow"}
+    ],
+    "limits": {"1": 2, "x": 3, "4": "5"},
+    "backup": {"name": "e", "port": 443, "mode": "Fast"}
+}"#;
+    let (config, errors) = from_str_collect_errors::<Config>(json);
+    let errors = errors.iter().map(|e| (e.to_string(), e.pointer().to_string(), &json[e.range().unwrap()])).collect::<Vec<_>>();
+    assert_eq!(errors, [
+        (String::from("unknown variant `Medium`, expected `Fast` or `Slow` at line 3 column 45"), String::from("/servers/0/mode"), "\"Medium\""),
+        (String::from("invalid type: integer `2`, expected a string at line 4 column 18"),          String::from("/servers/1/name"), "2"),
+        (String::from("port out of range at line 4 column 31"),                                     String::from("/servers/1/port"), "80"),
+        (String::from("invalid type: integer `1`, expected a string at line 5 column 62"),          String::from("/servers/2/tags/0"), "1"),
+        (String::from("missing field `port` at line 6 column 9"),                                   String::from("/servers/3"), "{\"name\": \"d\", \"prot\": 8082, \"mode\": \"Slow\"}"),
+        (String::from("unknown field `prot`, expected one of `name`, `port`, `mode`, `tags` at line 6 column 23"), String::from("/servers/3"), "\"prot\""),
+        (String::from("invalid type: string \"x\", expected u32 at line 8 column 24"),             String::from("/limits"), "\"x\""),
+        (String::from("invalid type: string \"5\", expected u32 at line 8 column 37"),             String::from("/limits/4"), "\"5\""),
+        (String::from("port out of range at line 9 column 37"),                                     String::from("/backup/port"), "443"),
+    ]);
+
+    let config = config.unwrap();
+    // servers[1] and servers[3] can't be built: the placeholder port `0` is out of range too
+    assert_eq!(config.servers.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), ["a", "c"]);
+    assert_eq!(config.servers[0].mode, Mode::Fast); // placeholder
+    assert_eq!(&json[config.servers[1].name.range()], "\"c\"");
+    assert_eq!(config.servers[1].tags, ["", "ok"]);
+    assert_eq!(config.limits.into_iter().collect::<Vec<_>>(), [(1, 2), (4, 0)]);
+    assert!(config.backup.is_none());
+
+    // An unfixable root still reports everything found along the way
+    let (servers, errors) = from_str_collect_errors::<[Server; 2]>(r#"[{"name": "a", "port": 1, "mode": "Fast"}, {"name": 2, "port": 1024, "mode": "Fast"}]"#);
+    assert!(servers.is_none());
+    assert_eq!(errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(), [
+        "port out of range at line 1 column 24",
+        "invalid type: integer `2`, expected a string at line 1 column 53",
+    ]);
+
+    let (value, errors) = from_str_collect_errors::<Config>("{\"servers\": [}");
+    assert!(value.is_none());
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].is_syntax());
+}
+
+#[test] fn collect_errors_sharing_offsets() {
+    fn sorted<'de, D: de::Deserializer<'de>>(deserializer: D) -> std::result::Result<Vec<u32>, D::Error> {
+        let ids = Spanned::<Vec<u32>>::deserialize(deserializer)?;
+        if ids.windows(2).any(|w| w[0] > w[1]) { return Err(error_at(ids.start() .. ids.start(), "unsorted")) }
+        Ok(ids.into_inner())
+    }
+    #[derive(Debug, Deserialize)] struct Leaf { #[serde(deserialize_with = "sorted")] ids: Vec<u32>, name: String }
+    #[derive(Debug, Deserialize)] struct Mid { leaf: Leaf }
+    #[derive(Debug, Deserialize)] struct Top { mid: Mid, other: Vec<Leaf> }
+
+    // Both missing fields are blamed on `{}`, and its placeholders share its range - as does the zero-width error at the start of `[[2, 1]]`
+    let json = r#"{"mid": {"leaf": {}}, "other": [{"ids": [[2, 1]], "name": "x"}, {"ids": [3, 1], "name": "y"}]}"#;
+    let (top, errors) = from_str_collect_errors::<Top>(json);
+    let errors = errors.iter().map(|e| (e.to_string(), e.pointer().to_string(), e.range().unwrap())).collect::<Vec<_>>();
+    assert_eq!(errors, [
+        (String::from("missing field `ids` at line 1 column 18"),                       String::from("/mid/leaf"), 17 .. 19),
+        (String::from("missing field `name` at line 1 column 18"),                      String::from("/mid/leaf"), 17 .. 19),
+        (String::from("invalid type: sequence, expected u32 at line 1 column 42"),      String::from("/other/0/ids/0"), 41 .. 47),
+        (String::from("unsorted at line 1 column 73"),                                  String::from("/other/1/ids"), 72 .. 72),
+    ]);
+
+    let top = top.unwrap();
+    assert_eq!((&top.mid.leaf.ids, top.mid.leaf.name.as_str()), (&vec![], ""));
+    assert_eq!(top.other.iter().map(|l| (&l.ids[..], l.name.as_str())).collect::<Vec<_>>(), [(&[0][..], "x"), (&[][..], "y")]);
+}
+
+#[test] fn ignored_keys() {
+    #[derive(Deserialize)] struct Server { #[allow(dead_code)] name: Spanned<String>, #[serde(default)] #[allow(dead_code)] port: u16 }
+    #[derive(Deserialize)] struct Config { #[allow(dead_code)] servers: Vec<Server>, #[allow(dead_code)] extra: Option<spanned::Value> }
+
+    let json = r#"{
+    "servers": [
+        {"name": "a", "prot": 80},
+        {"name": "b", "port": 81, "a/b~c": {"nested": [1, 2]}}
+    ],
+    "extra": {"kept": 1},
+    "verbose": true
+}"#;
+    let (_config, ignored) = from_str_report_ignored::<Config>(json).unwrap();
+    let ignored = ignored.iter().map(|i| (i.pointer(), i.key().as_str(), &json[i.key().range()])).collect::<Vec<_>>();
+    assert_eq!(ignored, [
+        ("/servers/0/prot",     "prot",     "\"prot\""),
+        ("/servers/1/a~1b~0c",  "a/b~c",    "\"a/b~c\""),
+        ("/verbose",            "verbose",  "\"verbose\""),
+    ]);
+
+    // Not tracked by default, nor by plain from_str
+    let mut de = Deserializer::from_str(json, &Settings::default());
+    let _ = Config::deserialize(&mut de).unwrap();
+    assert!(de.take_ignored_keys().is_empty());
+
+    // Escaped and unquoted keys, and readers
+    let json5 = "{servers: [{name: 'x', 'unu\\u0073ed': 1}]}";
+    let settings = Settings { allow_json5: true, ..Settings::default() };
+    let mut de = Deserializer::from_reader(json5.as_bytes(), &settings);
+    de.track_ignored_keys();
+    let _ = Config::deserialize(&mut de).unwrap();
+    let ignored = de.take_ignored_keys();
+    assert_eq!((ignored[0].pointer(), ignored[0].key().as_str(), &json5[ignored[0].key().range()]), ("/servers/0/unused", "unused", "'unu\\u0073ed'"));
+    assert!(de.take_ignored_keys().is_empty());
+
+    // Values of maps explicitly ignored are reported too
+    let (map, ignored) = from_str_report_ignored::<std::collections::BTreeMap<String, de::IgnoredAny>>("{\"a\": 1, \"b\": [2]}").unwrap();
+    assert_eq!(map.len(), 2);
+    assert_eq!(ignored.iter().map(|i| i.pointer()).collect::<Vec<_>>(), ["/a", "/b"]);
+}
+
+#[test] fn suggestions() {
+    #[derive(Debug, Deserialize)] enum Level { Debug, Info, Warning }
+    #[derive(Debug, Deserialize)] #[serde(deny_unknown_fields)] struct Config {
+        #[allow(dead_code)] timeout: u32,
+        #[allow(dead_code)] #[serde(default)] retries: u32,
+        #[allow(dead_code)] #[serde(default)] level: Option<Level>,
+    }
+
+    fn check(json: &str, message: &str, range: &str, suggestion: Option<&str>) {
+        for err in [from_str::<Config>(json).unwrap_err(), Config::deserialize(&from_str::<spanned::Value>(json).unwrap()).unwrap_err()].iter() {
+            assert!(err.to_string().starts_with(message), "{:?} should start with {:?}", err, message);
+            assert_eq!(&json[err.range().unwrap()], range);
+            assert_eq!(err.suggestion(), suggestion);
+        }
+    }
+
+    check(r#"{"timeuot": 1}"#,                  "unknown field `timeuot`, expected one of `timeout`, `retries`, `level`",   "\"timeuot\"",  Some("timeout"));
+    check(r#"{"timeout": 1, "retires": 2}"#,    "unknown field `retires`",                                                  "\"retires\"",  Some("retries"));
+    check(r#"{"timeout": 1, "Level": null}"#,   "unknown field `Level`",                                                    "\"Level\"",    Some("level"));
+    check(r#"{"timeout": 1, "verbose": true}"#, "unknown field `verbose`",                                                  "\"verbose\"",  None);
+    check(r#"{"timeout": 1, "level": "Warn"}"#, "unknown variant `Warn`, expected one of `Debug`, `Info`, `Warning`",       "\"Warn\"",     None);
+    check(r#"{"timeout": 1, "level": "Inof"}"#, "unknown variant `Inof`",                                                   "\"Inof\"",     Some("Info"));
+    check(r#"{"timeout": 1, "level": {"Warnign": null}}"#, "unknown variant `Warnign`",                                     "\"Warnign\"",  Some("Warning"));
+
+    let err = from_str::<Level>("\"Trace\"").unwrap_err();
+    assert_eq!(err.suggestion(), None);
+    assert_eq!((err.line(), err.column()), (1, 8));
+}
+
+#[test] fn error_pointers() {
+    use std::collections::BTreeMap;
+    #[derive(Debug, Deserialize)] enum Check { Http { #[allow(dead_code)] path: String }, Tcp(#[allow(dead_code)] u16) }
+    #[derive(Debug, Deserialize)] enum Kind { Alpha, Beta }
+    #[derive(Debug, Deserialize)] struct Server { #[allow(dead_code)] port: Option<Spanned<u16>>, #[allow(dead_code)] #[serde(default)] checks: Vec<Check>, #[allow(dead_code)] kind: Option<Kind> }
+    #[derive(Debug, Deserialize)] struct Config { #[allow(dead_code)] servers: BTreeMap<u32, Server> }
+
+    for (json, pointer) in [
+        (r#"{"servers":{"1":{"port":80},"2":{"port":-1}}}"#,                              "/servers/2/port"),
+        (r#"{"servers":{"1":{"checks":[{"Tcp":22},{"Http":{"path":5}}]}}}"#,               "/servers/1/checks/1/Http/path"),
+        (r#"{"servers":{"1":{"checks":[{"Tcp":22},{"Udp":53}]}}}"#,                        "/servers/1/checks/1"),
+        (r#"{"servers":{"1":{"checks":[{"Tcp":22} {"Tcp":23}]}}}"#,                        "/servers/1/checks"),
+        (r#"{"servers":{"1":{"checks":[{"Tcp":22}],"port":80,}}}"#,                        "/servers/1"),
+        (r#"{"servers":{"x":{}}}"#,                                                        "/servers"),
+        (r#"{"servers":{"1":{"kind":"Alpah","port":1}}}"#,                                 "/servers/1/kind"),
+    ].iter() {
+        let err = from_str::<Config>(json).unwrap_err();
+        assert_eq!(err.pointer(), *pointer, "json: {}", json);
+        // Same pointer for data errors when deserializing from a parsed value
+        if let Ok(value) = from_str::<spanned::Value>(json) {
+            assert_eq!(Config::deserialize(&value).unwrap_err().pointer(), *pointer, "json: {}", json);
+        }
+    }
+
+    let err = from_str::<Config>(r#"{"servers":{"1":{"port":"80"}}}"#).unwrap_err();
+    assert_eq!(format!("{:#}", err), "/servers/1/port: invalid type: string \"80\", expected u16 at line 1 column 29");
+    assert_eq!(format!("{}", err), "invalid type: string \"80\", expected u16 at line 1 column 29");
+    let err = from_str::<Config>("[]").unwrap_err();
+    assert_eq!(format!("{:#}", err), format!("{}", err));
+
+    #[derive(Debug, Deserialize)] struct Typo { #[allow(dead_code)] kind: Kind, #[allow(dead_code)] n: u32 }
+    let err = from_str::<Typo>(r#"{"kind": "Alpah", "n": 1}"#).unwrap_err();
+    assert_eq!(format!("{:#}", err), "/kind: unknown variant `Alpah`, expected `Alpha` or `Beta` at line 1 column 17");
+    assert_eq!(err.range(), Some(9 .. 16));
+
+    let mut stream = StreamDeserializer::<&str, Config>::new(r#"{"servers":{}} {"servers":{"1":{"port":true}}}"#);
+    assert!(stream.next().unwrap().is_ok());
+    assert_eq!(stream.next().unwrap().unwrap_err().pointer(), "/servers/1/port");
+}
+
+#[test] fn comments() {
+    let json = "// leading\r\n{\n    /* a */ \"a\": /* a value */ 1, // a trailing\n    // b\n    \"b\": [1, /* 0 */ 2 /* 1 */\n        // dangling b\n    ],\n    \"c\": {},\n} // trailing";
+    let settings = Settings { allow_comments: true, allow_trailing_comma: true, ..Settings::default() };
+    let (_value, comments) = from_str_with_comments(json, &settings).unwrap();
+
+    let texts = |comments: &[Comment]| comments.iter().map(|c| c.text().to_string()).collect::<Vec<_>>();
+    assert_eq!(texts(comments.leading("")),     vec![" leading"]);
+    assert_eq!(texts(comments.trailing("")),    vec![" trailing"]);
+    assert_eq!(texts(comments.leading("/a")),   vec![" a ", " a value "]);
+    assert_eq!(texts(comments.trailing("/a")),  vec![" a trailing"]);
+    assert_eq!(texts(comments.leading("/b")),   vec![" b"]);
+    assert_eq!(texts(comments.trailing("/b/0")),vec![" 0 "]);
+    assert_eq!(texts(comments.trailing("/b/1")),vec![" 1 "]);
+    assert_eq!(texts(comments.dangling("/b")),  vec![" dangling b"]);
+    assert!(comments.leading("/c").is_empty() && comments.dangling("/c").is_empty() && comments.dangling("").is_empty());
+
+    let all = comments.all();
+    assert_eq!(all.len(), 9);
+    assert_eq!(&json[all[0].range()], "// leading");
+    assert_eq!(&json[all[1].range()], "/* a */");
+    assert!(!all[0].is_block() && all[1].is_block());
+
+    // Comments are read the same way from an io::Read, and aren't duplicated by looking ahead for duplicate keys
+    #[derive(Deserialize)] #[allow(dead_code)] struct Config { a: u32, b: Vec<u32> }
+    let settings = Settings { duplicate_keys: Some(DuplicateKeys::KeepLast), ..settings };
+    let mut de = Deserializer::from_reader(Trickle(json.as_bytes()), &settings);
+    de.track_comments();
+    Config::deserialize(&mut de).unwrap();
+    de.end().unwrap();
+    let read = de.take_comments();
+    assert_eq!(read.iter().map(|c| c.range()).collect::<Vec<_>>(), all.iter().map(|c| c.range()).collect::<Vec<_>>());
+    assert_eq!(texts(&read), texts(all));
+
+    // Not tracked by default
+    let mut de = Deserializer::from_str(json, &settings);
+    spanned::Value::deserialize(&mut de).unwrap();
+    assert!(de.take_comments().is_empty());
+}
+
+#[test] fn document_edits() {
+    let settings = Settings { allow_comments: true, allow_trailing_comma: true, ..Settings::default() };
+    let json = "{\n  // first\n  \"a\": 1, // one\n  \"b\": [1, 2, 3],\n  /* last */\n  \"c\": {}\n}\n";
+    let edit = |f: &dyn Fn(&mut Document) -> Result<()>| { let mut doc = Document::parse(json, &settings).unwrap(); f(&mut doc).unwrap(); doc.into_string() };
+
+    assert_eq!(edit(&|d| d.remove("/a")),           "{\n  \"b\": [1, 2, 3],\n  /* last */\n  \"c\": {}\n}\n");
+    assert_eq!(edit(&|d| d.remove("/c")),           "{\n  // first\n  \"a\": 1, // one\n  \"b\": [1, 2, 3]\n}\n");
+    assert_eq!(edit(&|d| d.remove("/b/0")),         "{\n  // first\n  \"a\": 1, // one\n  \"b\": [2, 3],\n  /* last */\n  \"c\": {}\n}\n");
+    assert_eq!(edit(&|d| d.remove("/b/2")),         "{\n  // first\n  \"a\": 1, // one\n  \"b\": [1, 2],\n  /* last */\n  \"c\": {}\n}\n");
+    assert_eq!(edit(&|d| d.insert("/b/1", "x")),    "{\n  // first\n  \"a\": 1, // one\n  \"b\": [1, \"x\", 2, 3],\n  /* last */\n  \"c\": {}\n}\n");
+    assert_eq!(edit(&|d| d.insert("/c/d", [1])),    "{\n  // first\n  \"a\": 1, // one\n  \"b\": [1, 2, 3],\n  /* last */\n  \"c\": {\"d\": [1]}\n}\n");
+    assert_eq!(edit(&|d| d.insert("/d", 4)),        "{\n  // first\n  \"a\": 1, // one\n  \"b\": [1, 2, 3],\n  /* last */\n  \"c\": {},\n  \"d\": 4\n}\n");
+    assert_eq!(edit(&|d| d.set("/a", "~")),         "{\n  // first\n  \"a\": \"~\", // one\n  \"b\": [1, 2, 3],\n  /* last */\n  \"c\": {}\n}\n");
+    assert_eq!(edit(&|d| d.rename("/a", "/")),      "{\n  // first\n  \"/\": 1, // one\n  \"b\": [1, 2, 3],\n  /* last */\n  \"c\": {}\n}\n");
+    assert_eq!(edit(&|d| d.set("", false)),         "false\n");
+
+    // Spans are kept up to date, and pointers are escaped
+    let mut doc = Document::parse(json, &settings).unwrap();
+    doc.rename("/a", "x/y").unwrap();
+    doc.set("/x~1y", 1234).unwrap();
+    assert_eq!(&doc.as_str()[doc.value().pointer("/x~1y").unwrap().range()], "1234");
+    assert_eq!(doc.comments().trailing("/x~1y")[0].text(), " one");
+
+    // Failed edits leave the document untouched
+    for (result, message) in [
+        (doc.set("/nope", 1),           "`/nope` not found"),
+        (doc.insert("/b", 1),           "`/b` already exists"),
+        (doc.insert("/b/4", 1),         "`/b/4` is not a valid array index"),
+        (doc.insert("/x~1y/z", 1),      "`/x~1y` is not an array or object"),
+        (doc.rename("/x~1y", "b"),      "`/x~1y` can't be renamed to \"b\", which already exists"),
+        (doc.remove(""),                "the root value has no parent"),
+        (doc.remove("b"),               "`b` is not a JSON pointer"),
+    ].iter() {
+        assert_eq!(result.as_ref().unwrap_err().to_string(), *message);
+    }
+    assert_eq!(doc.as_str(), "{\n  // first\n  \"x/y\": 1234, // one\n  \"b\": [1, 2, 3],\n  /* last */\n  \"c\": {}\n}\n");
+
+    // Trailing commas and single line layouts
+    let mut doc = Document::parse("[1, 2,]", &settings).unwrap();
+    doc.insert("/-", 3).unwrap();
+    assert_eq!(doc.as_str(), "[1, 2, 3,]");
+    doc.remove("/0").unwrap();
+    assert_eq!(doc.as_str(), "[2, 3,]");
+    let mut doc = Document::parse("{\"a\": 1,\n}", &settings).unwrap();
+    doc.insert("/b", 2).unwrap();
+    assert_eq!(doc.as_str(), "{\"a\": 1, \"b\": 2,\n}");
+    doc.remove("/a").unwrap();
+    doc.remove("/b").unwrap();
+    assert_eq!(doc.as_str(), "{\n}");
+    doc.insert("/c", 3).unwrap();
+    assert_eq!(doc.as_str(), "{\"c\": 3\n}");
+
+    // Multi-line block comments after the last member are kept whole
+    let mut doc = Document::parse("{\n  \"a\": 1 /* x\n y */\n}", &settings).unwrap();
+    doc.insert("/b", 2).unwrap();
+    assert_eq!(doc.as_str(), "{\n  \"a\": 1, /* x\n y */\n  \"b\": 2\n}");
+    let mut doc = Document::parse("{\n  \"a\": 1, /* x\n y */\n}", &settings).unwrap();
+    doc.insert("/b", 2).unwrap();
+    assert_eq!(doc.as_str(), "{\n  \"a\": 1, /* x\n y */\n  \"b\": 2,\n}");
+}
+
+#[test] fn formatter() {
+    let settings = Settings { allow_comments: true, allow_trailing_comma: true, allow_json5: true, ..Settings::default() };
+    let json = "// header\n{ /* a */ a: 0x1, b: [1,2,3], // bee\n\"c\": [{}, [], {\"d\": 'e'}], \"f\": [ // none\n], \"g\": {},\n} // end";
+    let fmt = |json: &str, options: &FormatOptions| {
+        let formatted = format(json, &settings, options).unwrap();
+        assert_eq!(format(&formatted, &settings, options).unwrap(), formatted, "not idempotent");
+        formatted
+    };
+
+    assert_eq!(fmt(json, &FormatOptions::default()), "// header\n{\n    /* a */\n    a: 0x1,\n    b: [1, 2, 3], // bee\n    \"c\": [\n        {},\n        [],\n        {\n            \"d\": 'e'\n        }\n    ],\n    \"f\": [\n        // none\n    ],\n    \"g\": {}\n}\n// end\n");
+    assert_eq!(fmt(json, &FormatOptions { tabs: true, trailing_commas: TrailingCommas::Multiline, ..Default::default() }), "// header\n{\n\t/* a */\n\ta: 0x1,\n\tb: [1, 2, 3], // bee\n\t\"c\": [\n\t\t{},\n\t\t[],\n\t\t{\n\t\t\t\"d\": 'e',\n\t\t},\n\t],\n\t\"f\": [\n\t\t// none\n\t],\n\t\"g\": {},\n}\n// end\n");
+
+    // Array wrapping
+    let json = "[1, 22, 333, 4444, 55555]";
+    assert_eq!(fmt(json, &FormatOptions::default()), "[1, 22, 333, 4444, 55555]\n");
+    assert_eq!(fmt(json, &FormatOptions { array_wrap: ArrayWrap::Always, ..Default::default() }), "[\n    1,\n    22,\n    333,\n    4444,\n    55555\n]\n");
+    assert_eq!(fmt(json, &FormatOptions { indent: 2, line_width: 12, ..Default::default() }), "[\n  1,\n  22,\n  333,\n  4444,\n  55555\n]\n");
+    assert_eq!(fmt(json, &FormatOptions { indent: 2, line_width: 12, array_wrap: ArrayWrap::Fill, ..Default::default() }), "[\n  1, 22,\n  333, 4444,\n  55555\n]\n");
+    assert_eq!(fmt(json, &FormatOptions { indent: 2, line_width: 12, array_wrap: ArrayWrap::Fill, trailing_commas: TrailingCommas::Multiline, ..Default::default() }), "[\n  1, 22,\n  333, 4444,\n  55555,\n]\n");
+
+    assert_eq!(fmt("\"scalar\" /* only */", &FormatOptions::default()), "\"scalar\"\n/* only */\n");
+    assert!(format("{\"a\": 1,}", &Settings::default(), &FormatOptions::default()).is_err());
+
+    // Keeping only one of several duplicates would lose content
+    let settings = Settings { duplicate_keys: Some(DuplicateKeys::KeepLast), ..Settings::default() };
+    let err = format("{\"a\": 1, \"a\": 2}", &settings, &FormatOptions::default()).unwrap_err();
+    assert_eq!((err.range(), err.duplicate_of()), (Some(9 .. 12), Some(1 .. 4)));
+}
+
+#[test] fn strip_jsonc() {
+    for (jsonc, json) in [
+        ("[1, 2,]",                                     "[1, 2 ]"),
+        ("{\"a\": 1 , // é\n}",                          "{\"a\": 1        \n}"), // é is 2 bytes
+        ("[\"//\", \"/*\", \"\\\",\" /* ] */ ,]",       "[\"//\", \"/*\", \"\\\",\"          ]"),
+        ("[it's, // c\n1,]",                             "[it's,     \n1 ]"), // JSONC has no single quoted strings
+        ("/* a\r\nb */ [[],[{},],]",                    "    \r\n     [[],[{} ] ]"),
+        ("1 /* unterminated",                           "1                "),
+        ("[1,\n// ,]\n]",                               "[1 \n     \n]"),
+    ].iter() {
+        let stripped = crate::strip_jsonc(jsonc);
+        assert_eq!(stripped, *json, "jsonc: {:?}", jsonc);
+        assert_eq!(stripped.len(), jsonc.len());
+    }
+    assert!(from_str::<serde_json::Value>(&crate::strip_jsonc("{\"a\": [1, /**/ 2,], // x\n}")).is_ok());
+}
+
+#[test] fn from_str_partial() {
+    let settings = Settings::default();
+    for (json, expected, errors) in [
+        ("{\"a\": [1, 2], \"b\": null}",        r#"{"a":[1,2],"b":null}"#,          &[][..]),
+        ("[1,,2]",                              r#"[1,null,2]"#,                    &[("expected value", "/1", 3 .. 4)][..]),
+        ("[1, 2,]",                             r#"[1,2]"#,                         &[("trailing comma", "", 6 .. 7)][..]),
+        ("[1 2",                                r#"[1,2]"#,                         &[("expected `,` or `]`", "", 3 .. 4), ("EOF while parsing a list", "", 4 .. 4)][..]),
+        ("[@#, 2]",                             r#"[null,2]"#,                      &[("expected value", "/0", 1 .. 3)][..]),
+        ("[x 1]",                               r#"[1]"#,                           &[("expected value", "/0", 1 .. 2)][..]),
+        ("[01, \"a\\q\", \"b\n]",               r#"[null,null,null]"#,              &[("invalid number", "/0", 2 .. 3), ("invalid escape", "/1", 8 .. 9), ("control character (\\u0000-\\u001F) found while parsing a string", "/2", 14 .. 15)][..]),
+        ("{\"a\": [1, 2}",                      r#"{"a":[1,2]}"#,                   &[("expected `,` or `]`", "/a", 11 .. 12)][..]),
+        ("{\"a\" 1, : 2, 3: 4, \"b\":}",        r#"{"a":1,"b":null}"#,              &[("expected `:`", "/a", 5 .. 6), ("key must be a string", "", 8 .. 9), ("key must be a string", "", 13 .. 14), ("expected value", "/b", 23 .. 24)][..]),
+        ("{\"a\": 1, \"a\": 2}",                r#"{"a":1}"#,                       &[("duplicate key `a`", "/a", 9 .. 12)][..]),
+        ("{\"a\": 1} 2",                        r#"{"a":1}"#,                       &[("trailing characters", "", 9 .. 10)][..]),
+        ("",                                    r#"null"#,                          &[("EOF while parsing a value", "", 0 .. 0)][..]),
+    ].iter() {
+        let (value, actual) = crate::from_str_partial(json, &settings);
+        assert_eq!(serde_json::Value::from(value), serde_json::from_str::<serde_json::Value>(expected).unwrap(), "json: {:?}", json);
+        let actual = actual.iter().map(|e| (e.to_string().split(" at line").next().unwrap().to_string(), e.pointer().to_string(), e.range().unwrap())).collect::<Vec<_>>();
+        let errors = errors.iter().map(|(msg, ptr, range)| (msg.to_string(), ptr.to_string(), range.clone())).collect::<Vec<_>>();
+        assert_eq!(actual, errors, "json: {:?}", json);
+        assert_eq!(errors.is_empty(), from_str::<spanned::Value>(json).is_ok(), "json: {:?}", json);
+    }
+
+    // Spans of recovered values, and placeholders
+    let json = "[1, , {\"b\": 2";
+    let (value, errors) = crate::from_str_partial(json, &settings);
+    assert_eq!(errors.len(), 3);
+    assert_eq!(value.range(), 0 .. json.len());
+    assert_eq!(value.pointer("/1").unwrap().range(), 4 .. 4);
+    assert_eq!(&json[value.pointer("/2/b").unwrap().range()], "2");
+
+    // Settings are honored
+    let json5 = Settings { allow_json5: true, ..Settings::default() };
+    let (value, errors) = crate::from_str_partial("{a: 'x', b: +Infinity, /* c: */ d: [0x10,],}", &json5);
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(value.pointer("/d/0").unwrap().as_number().unwrap().as_u64(), Some(16));
+    let (_, errors) = crate::from_str_partial("[1 /* unterminated", &json5);
+    assert_eq!(errors.iter().map(|e| e.to_string().split(" at line").next().unwrap().to_string()).collect::<Vec<_>>(), ["EOF while parsing a comment", "EOF while parsing a list"]);
+
+    // Too deeply nested
+    let (_, errors) = crate::from_str_partial(&"[".repeat(200), &settings);
+    assert_eq!(errors[0].to_string(), "recursion limit exceeded at line 1 column 129");
+}
+
+#[test] fn tokenizer() {
+    use TokenKind::*;
+    let tokens = |json: &str, settings: &Settings| Tokenizer::new(json, settings).map(|t| t.map(|t| (t.kind(), t.range())).map_err(|e| (e.to_string(), e.range().unwrap()))).collect::<Vec<_>>();
+
+    let json = "{\"a\\n\": [-1.5e3, null,false]}\r\n";
+    assert_eq!(tokens(json, &Settings::default()), [
+        Ok((BeginObject, 0 .. 1)), Ok((String, 1 .. 6)), Ok((Colon, 6 .. 7)), Ok((Whitespace, 7 .. 8)), Ok((BeginArray, 8 .. 9)),
+        Ok((Number, 9 .. 15)), Ok((Comma, 15 .. 16)), Ok((Whitespace, 16 .. 17)), Ok((Null, 17 .. 21)), Ok((Comma, 21 .. 22)), Ok((False, 22 .. 27)),
+        Ok((EndArray, 27 .. 28)), Ok((EndObject, 28 .. 29)), Ok((Whitespace, 29 .. 31)),
+    ]);
+
+    let json5 = Settings { allow_json5: true, ..Settings::default() };
+    let json = "{key: 'v', n: [0x1F, +.5, -Infinity, NaN,],}\u{A0}// line\r\n/* block */";
+    let kinds = tokens(json, &json5).into_iter().map(|t| t.unwrap().0).filter(|k| !k.is_trivia()).collect::<Vec<_>>();
+    assert_eq!(kinds, [BeginObject, Identifier, Colon, String, Comma, Identifier, Colon, BeginArray, Number, Comma, Number, Comma, Number, Comma, Number, Comma, EndArray, Comma, EndObject]);
+    let trivia = tokens(json, &json5).into_iter().map(|t| t.unwrap()).filter(|(k, _)| k.is_trivia()).skip(6).collect::<Vec<_>>();
+    assert_eq!(trivia, [(Whitespace, 44 .. 46), (LineComment, 46 .. 53), (Whitespace, 53 .. 55), (BlockComment, 55 .. 66)]);
+
+    // Malformed tokens are skipped after their error
+    assert_eq!(tokens("[01, \"\\q\", key, /, @x]", &Settings::default()), [
+        Ok((BeginArray, 0 .. 1)), Err(("invalid number at line 1 column 3".into(), 2 .. 3)), Ok((Comma, 3 .. 4)), Ok((Whitespace, 4 .. 5)),
+        Err(("invalid escape at line 1 column 8".into(), 7 .. 8)), Ok((Comma, 9 .. 10)), Ok((Whitespace, 10 .. 11)),
+        Err(("expected value at line 1 column 12".into(), 11 .. 14)), Ok((Comma, 14 .. 15)), Ok((Whitespace, 15 .. 16)),
+        Err(("unexpected character at line 1 column 17".into(), 16 .. 17)), Ok((Comma, 17 .. 18)), Ok((Whitespace, 18 .. 19)),
+        Err(("unexpected character at line 1 column 20".into(), 19 .. 20)), Ok((EndArray, 21 .. 22)),
+    ]);
+    assert_eq!(tokens("1 /* open", &json5).pop().unwrap(), Err(("EOF while parsing a comment at line 1 column 3".into(), 2 .. 9)));
+}
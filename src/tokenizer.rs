@@ -0,0 +1,105 @@
+use crate::{Deserializer, Result, Settings, SliceRead};
+
+use std::ops::Range;
+
+
+
+/// What a [Token] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// `[`
+    BeginArray,
+    /// `]`
+    EndArray,
+    /// `{`
+    BeginObject,
+    /// `}`
+    EndObject,
+    /// `:`
+    Colon,
+    /// `,`
+    Comma,
+    /// `"string"`, or JSON5's `'string'` - including its quotes
+    String,
+    /// `123`, `-1.5e3`, or JSON5's `0x1F`, `+.5`, `Infinity`, `NaN`...
+    Number,
+    /// `null`
+    Null,
+    /// `true`
+    True,
+    /// `false`
+    False,
+    /// A JSON5 unquoted object key such as `foo`.  Whether it's actually followed by a `:` isn't checked.
+    Identifier,
+    /// `// comment`, not including the end of the line
+    LineComment,
+    /// `/* comment */`
+    BlockComment,
+    /// Consecutive whitespace (including JSON5's)
+    Whitespace,
+}
+
+impl TokenKind {
+    /// `true` for [whitespace](Self::Whitespace) and comments, which a parser would skip.
+    pub fn is_trivia(self) -> bool { matches!(self, TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment) }
+}
+
+/// A [TokenKind] and where it is.  See [Tokenizer].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Token {
+    kind:   TokenKind,
+    range:  Range<usize>,
+}
+
+impl Token {
+    pub(crate) fn new(kind: TokenKind, range: Range<usize>) -> Self { Self { kind, range } }
+
+    /// What the token is.
+    pub fn kind(&self) -> TokenKind { self.kind }
+
+    /// **Zero**-based byte range of the token.
+    pub fn range(&self) -> Range<usize> { self.range.clone() }
+}
+
+
+
+/// Splits json into [Token]s, per [Settings] - for syntax highlighting or linting, without a full parse.
+///
+/// Tokens cover every byte of valid input, including whitespace and comments.  Strings and numbers are validated
+/// like [Deserializer] does, but their order isn't: `] 1 :` is as fine as `[1]`.  A malformed token yields an error
+/// (whose [range](crate::Error::range) is just the problem, like an invalid escape) instead.  Its bytes - up to the next
+/// whitespace or punctuation, or the end of the line for strings - are then skipped, belonging to no token, and tokenizing continues after them.
+///
+/// ```
+/// # use json_spanned_value::{self as jsv, TokenKind};
+/// let json = "{\"a\": [1, true]} // done";
+/// let settings = jsv::Settings { allow_comments: true, ..Default::default() };
+/// let tokens = jsv::Tokenizer::new(json, &settings).map(|t| t.unwrap()).filter(|t| !t.kind().is_trivia()).collect::<Vec<_>>();
+/// assert_eq!(tokens.iter().map(|t| t.kind()).collect::<Vec<_>>(), [
+///     TokenKind::BeginObject, TokenKind::String, TokenKind::Colon,
+///     TokenKind::BeginArray, TokenKind::Number, TokenKind::Comma, TokenKind::True, TokenKind::EndArray,
+///     TokenKind::EndObject,
+/// ]);
+/// assert_eq!(&json[tokens[1].range()], "\"a\"");
+///
+/// let errors = jsv::Tokenizer::new("[1, @, 2]", &settings).filter_map(|t| t.err()).collect::<Vec<_>>();
+/// assert_eq!(errors[0].to_string(), "unexpected character at line 1 column 5");
+///
+/// let after = jsv::Tokenizer::new("[@x, 2]", &settings).filter_map(|t| t.ok()).map(|t| t.range()).collect::<Vec<_>>();
+/// assert_eq!(after, [0 .. 1, 3 .. 4, 4 .. 5, 5 .. 6, 6 .. 7]); // "@x" is skipped
+/// ```
+pub struct Tokenizer<'a> {
+    de: Deserializer<'a, SliceRead<'a>>,
+}
+
+impl<'a> Tokenizer<'a> {
+    /// Tokenize `json`.
+    pub fn new(json: &'a str, settings: &Settings) -> Self {
+        Self { de: Deserializer::from_str(json, settings) }
+    }
+}
+
+impl Iterator for Tokenizer<'_> {
+    type Item = Result<Token>;
+    fn next(&mut self) -> Option<Self::Item> { self.de.next_token() }
+}
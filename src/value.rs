@@ -1,9 +1,10 @@
 use crate::*;
 
-use serde::de;
+use serde::{de, ser};
 
 use std::convert::*;
 use std::fmt::{self, Debug, Formatter};
+use std::result::Result;
 
 
 
@@ -19,12 +20,12 @@ pub enum Value {
 }
 
 impl Value {
-    #[doc="True if self is `null`"                                          ] pub fn is_null    (&self) -> bool { match self { Value::Null      => true, _ => false } }
-    #[doc="True if self is `true` or `false`"                               ] pub fn is_bool    (&self) -> bool { match self { Value::Bool(_)   => true, _ => false } }
-    #[doc="True if self is a number like `123`"                             ] pub fn is_number  (&self) -> bool { match self { Value::Number(_) => true, _ => false } }
-    #[doc="True if self is a string like `\"asdf\"`"                        ] pub fn is_string  (&self) -> bool { match self { Value::String(_) => true, _ => false } }
-    #[doc="True if self is an array like `[1, 2, 3]`"                       ] pub fn is_array   (&self) -> bool { match self { Value::Array(_)  => true, _ => false } }
-    #[doc="True if self is an object like `{\"a\": 1, \"b\": 2}`"           ] pub fn is_object  (&self) -> bool { match self { Value::Object(_) => true, _ => false } }
+    #[doc="True if self is `null`"                                          ] pub fn is_null    (&self) -> bool { matches!(self, Value::Null) }
+    #[doc="True if self is `true` or `false`"                               ] pub fn is_bool    (&self) -> bool { matches!(self, Value::Bool(_)) }
+    #[doc="True if self is a number like `123`"                             ] pub fn is_number  (&self) -> bool { matches!(self, Value::Number(_)) }
+    #[doc="True if self is a string like `\"asdf\"`"                        ] pub fn is_string  (&self) -> bool { matches!(self, Value::String(_)) }
+    #[doc="True if self is an array like `[1, 2, 3]`"                       ] pub fn is_array   (&self) -> bool { matches!(self, Value::Array(_)) }
+    #[doc="True if self is an object like `{\"a\": 1, \"b\": 2}`"           ] pub fn is_object  (&self) -> bool { matches!(self, Value::Object(_)) }
  
     #[doc="`Some(()) if self is `null`"                                     ] pub fn as_null      (&self) -> Option<()>                                       { match self { Value::Null      => Some(()), _ => None } }
     #[doc="`Some(inner)` if self is `true` or `false`"                      ] pub fn as_bool      (&self) -> Option<bool>                                     { match self { Value::Bool(v)   => Some(*v), _ => None } }
@@ -60,6 +61,59 @@ impl Value {
     }
 }
 
+impl Spanned<Value> {
+    /// The innermost value whose [range](Spanned::range) contains the byte `offset`, such as the value under an editor's cursor.
+    /// An `offset` within an object's key resolves to that key's value.
+    /// Returns `None` if `offset` is outside of `self`.
+    pub fn value_at(&self, offset: usize) -> Option<&Spanned<Value>> {
+        if !self.range().contains(&offset) { return None }
+        let child = match self.get_ref() {
+            Value::Array(a)     => a.iter().find_map(|v| v.value_at(offset)),
+            Value::Object(o)    => o.iter().find_map(|(k, v)| if k.range().contains(&offset) { Some(v) } else { v.value_at(offset) }),
+            _other              => None,
+        };
+        Some(child.unwrap_or(self))
+    }
+}
+
+impl From<serde_json::Value> for Value {
+    /// Children get `0 .. 0` spans, as if deserialized by a non-[crate::Deserializer].
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null         => Value::Null,
+            serde_json::Value::Bool(b)      => Value::Bool(b),
+            serde_json::Value::Number(n)    => Value::Number(n),
+            serde_json::Value::String(s)    => Value::String(s),
+            serde_json::Value::Array(a)     => Value::Array(a.into_iter().map(|v| Spanned::from(Value::from(v))).collect()),
+            serde_json::Value::Object(o)    => Value::Object(o.into_iter().map(|(k, v)| (Spanned::from(k), Spanned::from(Value::from(v)))).collect()),
+        }
+    }
+}
+
+impl From<serde_json::Value> for Spanned<Value> {
+    /// `self` and children get `0 .. 0` spans, as if deserialized by a non-[crate::Deserializer].
+    fn from(value: serde_json::Value) -> Self { Spanned::from(Value::from(value)) }
+}
+
+impl From<Value> for serde_json::Value {
+    /// Discards the spans of all children.
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null         => serde_json::Value::Null,
+            Value::Bool(b)      => serde_json::Value::Bool(b),
+            Value::Number(n)    => serde_json::Value::Number(n),
+            Value::String(s)    => serde_json::Value::String(s),
+            Value::Array(a)     => serde_json::Value::Array(a.into_iter().map(|v| v.into_inner().into()).collect()),
+            Value::Object(o)    => serde_json::Value::Object(o.into_iter().map(|(k, v)| (k.into_inner(), v.into_inner().into())).collect()),
+        }
+    }
+}
+
+impl From<Spanned<Value>> for serde_json::Value {
+    /// Discards the spans of `value` and all children.
+    fn from(value: Spanned<Value>) -> Self { value.into_inner().into() }
+}
+
 impl Debug for Value {
     fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
         match self {
@@ -102,9 +156,22 @@ impl Debug for Value {
     }
 }
 
+impl ser::Serialize for Value {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Null         => serializer.serialize_unit(),
+            Value::Bool(b)      => serializer.serialize_bool(*b),
+            Value::Number(n)    => n.serialize(serializer),
+            Value::String(s)    => serializer.serialize_str(s),
+            Value::Array(a)     => serializer.collect_seq(a),
+            Value::Object(o)    => o.serialize(serializer),
+        }
+    }
+}
+
 impl<'de> de::Deserialize<'de> for Value {
     fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
-        struct ValueVisitor;
+        struct ValueVisitor { unique_keys: bool }
         impl<'de> de::Visitor<'de> for ValueVisitor {
             type Value = Value;
             fn expecting(&self, formatter: &mut Formatter) -> fmt::Result { formatter.write_str("any valid JSON value") }
@@ -119,6 +186,7 @@ impl<'de> de::Deserialize<'de> for Value {
             fn visit_unit   <E>(self)                   -> Result<Value, E> { Ok(Value::Null) }
 
             fn visit_some<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> { de::Deserialize::deserialize(deserializer) }
+            fn visit_newtype_struct<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> { deserializer.deserialize_any(ValueVisitor { unique_keys: true }) }
 
             fn visit_seq<V: de::SeqAccess<'de>>(self, mut visitor: V) -> Result<Value, V::Error> {
                 let mut vec = Vec::new();
@@ -129,7 +197,7 @@ impl<'de> de::Deserialize<'de> for Value {
             fn visit_map<V: de::MapAccess<'de>>(self, mut visitor: V) -> Result<Value, V::Error> {
                 let mut values = Map::new();
                 while let Some(key) = visitor.next_key()? {
-                    if !settings().map_or(false, |s| s.allow_duplicate_keys) && values.contains_key(&key) {
+                    if self.unique_keys && values.contains_key(&key) {
                         return Err(de::Error::custom(format!("Duplicate field: {:?}", key)));
                     }
                     let value = visitor.next_value()?;
@@ -139,6 +207,6 @@ impl<'de> de::Deserialize<'de> for Value {
             }
         }
 
-        deserializer.deserialize_any(ValueVisitor)
+        deserializer.deserialize_newtype_struct(map::UNIQUE_KEYS, ValueVisitor { unique_keys: false })
     }
 }
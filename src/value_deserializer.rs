@@ -0,0 +1,370 @@
+use crate::{spanned, Error, Map, Result, Spanned, Value};
+use crate::repair::{Placeholder, Repairs};
+
+use serde::de::{self, Visitor, DeserializeSeed, IntoDeserializer, Unexpected};
+use serde::de::value::BorrowedStrDeserializer;
+
+
+
+macro_rules! forward_to_node {
+    ($( $method:ident ( $($arg:ident : $ty:ty),* ); )*) => {$(
+        fn $method<V: Visitor<'de>>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value> { Node::new(self, None).$method($($arg,)* visitor) }
+    )*};
+}
+
+/// Deserialize from an already parsed [spanned::Value].
+/// [Spanned] values keep their original spans, and errors get the [range](Error::range) and [pointer](Error::pointer) of the offending value.
+impl<'de> de::Deserializer<'de> for &'de spanned::Value {
+    type Error = Error;
+
+    forward_to_node! {
+        deserialize_any(); deserialize_bool(); deserialize_char(); deserialize_str(); deserialize_string();
+        deserialize_i8(); deserialize_i16(); deserialize_i32(); deserialize_i64(); deserialize_i128();
+        deserialize_u8(); deserialize_u16(); deserialize_u32(); deserialize_u64(); deserialize_u128();
+        deserialize_f32(); deserialize_f64(); deserialize_bytes(); deserialize_byte_buf();
+        deserialize_option(); deserialize_unit(); deserialize_unit_struct(name: &'static str); deserialize_newtype_struct(name: &'static str);
+        deserialize_seq(); deserialize_tuple(len: usize); deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map(); deserialize_struct(name: &'static str, fields: &'static [&'static str]);
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]); deserialize_identifier(); deserialize_ignored_any();
+    }
+}
+
+
+
+macro_rules! or_placeholder {
+    ($( $method:ident ( $($arg:ident : $ty:ty),* ); )*) => {$(
+        fn $method<V: Visitor<'de>>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value> {
+            match self.placeholder() {
+                Some(placeholder)   => placeholder.$method($($arg,)* visitor),
+                None                => self.deserialize_any(visitor),
+            }
+        }
+    )*};
+}
+
+/// A [spanned::Value] being deserialized, with any [Repairs] made to the document by [crate::from_str_collect_errors].
+#[derive(Clone, Copy)]
+pub(crate) struct Node<'de> {
+    value:      &'de spanned::Value,
+    repairs:    Option<&'de Repairs>,
+}
+
+impl<'de> Node<'de> {
+    pub(crate) fn new(value: &'de spanned::Value, repairs: Option<&'de Repairs>) -> Self { Self { value, repairs } }
+
+    fn child(&self, value: &'de spanned::Value) -> Self { Self { value, repairs: self.repairs } }
+
+    fn is_skipped<T>(&self, key_or_element: &T) -> bool { matches!(self.repairs, Some(repairs) if repairs.is_skipped(key_or_element)) }
+
+    fn placeholder(&self) -> Option<Placeholder> {
+        let repairs = self.repairs?;
+        if repairs.is_placeholder(self.value) { Some(Placeholder::new(self.value.range())) } else { None }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Node<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if let Some(placeholder) = self.placeholder() { return placeholder.deserialize_any(visitor) }
+        let value = match self.value.get_ref() {
+            Value::Null         => visitor.visit_unit(),
+            Value::Bool(b)      => visitor.visit_bool(*b),
+            Value::Number(n)    => visit_number(n, visitor),
+            Value::String(s)    => visitor.visit_borrowed_str(s),
+            Value::Array(a)     => {
+                let mut seq = SeqAccess { node: self, iter: a.iter().enumerate() };
+                visitor.visit_seq(&mut seq).and_then(|value| match seq.remaining() {
+                    0           => Ok(value),
+                    remaining   => Err(de::Error::invalid_length(a.len() - remaining, &"fewer elements in array")),
+                })
+            },
+            Value::Object(o)    => {
+                let added = self.repairs.map_or(&[][..], |repairs| repairs.added(self.value));
+                let mut map = MapAccess { node: self, iter: o.into_iter(), added: added.iter(), value: None };
+                visitor.visit_map(&mut map).and_then(|value| match map.remaining() {
+                    0           => Ok(value),
+                    remaining   => Err(de::Error::invalid_length(o.len() - remaining, &"fewer elements in map")),
+                })
+            },
+        };
+        value.map_err(|err| at_node(self.value, err))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if let Some(placeholder) = self.placeholder() { return placeholder.deserialize_option(visitor) }
+        match self.value.get_ref() {
+            Value::Null => visitor.visit_none().map_err(|err| at_node(self.value, err)),
+            _other      => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V) -> Result<V::Value> {
+        if name == spanned::NAME {
+            visitor.visit_map(SpannedAccess::new(self.value.range(), self))
+        } else if let Some(placeholder) = self.placeholder() {
+            placeholder.deserialize_newtype_struct(name, visitor)
+        } else {
+            visitor.visit_newtype_struct(self)
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        if let Some(placeholder) = self.placeholder() { return placeholder.deserialize_enum(name, variants, visitor) }
+        let value = match self.value.get_ref() {
+            Value::String(s)                => visitor.visit_enum(BorrowedStrDeserializer::new(s)),
+            Value::Object(o) if o.len() == 1 => {
+                let (key, value) = o.iter().next().unwrap();
+                visitor.visit_enum(VariantAccess { key, value: self.child(value) })
+            },
+            _other                          => Err(de::Error::invalid_type(unexpected(self.value), &"string or map")),
+        };
+        value.map_err(|err| at_node(self.value, err))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    or_placeholder! {
+        deserialize_bool(); deserialize_char(); deserialize_str(); deserialize_string();
+        deserialize_i8(); deserialize_i16(); deserialize_i32(); deserialize_i64(); deserialize_i128();
+        deserialize_u8(); deserialize_u16(); deserialize_u32(); deserialize_u64(); deserialize_u128();
+        deserialize_f32(); deserialize_f64(); deserialize_bytes(); deserialize_byte_buf();
+        deserialize_unit(); deserialize_unit_struct(name: &'static str);
+        deserialize_seq(); deserialize_tuple(len: usize); deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map(); deserialize_struct(name: &'static str, fields: &'static [&'static str]); deserialize_identifier();
+    }
+}
+
+fn visit_number<'de, V: Visitor<'de>>(n: &serde_json::Number, visitor: V) -> Result<V::Value> {
+    match (n.as_u64(), n.as_i64()) {
+        (Some(u), _)    => visitor.visit_u64(u),
+        (None, Some(i)) => visitor.visit_i64(i),
+        (None, None)    => visitor.visit_f64(n.as_f64().unwrap_or(f64::NAN)),
+    }
+}
+
+fn unexpected(value: &Value) -> Unexpected<'_> {
+    match value {
+        Value::Null         => Unexpected::Unit,
+        Value::Bool(b)      => Unexpected::Bool(*b),
+        Value::Number(n)    => match (n.as_u64(), n.as_i64()) {
+            (Some(u), _)    => Unexpected::Unsigned(u),
+            (None, Some(i)) => Unexpected::Signed(i),
+            (None, None)    => Unexpected::Float(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(s)    => Unexpected::Str(s),
+        Value::Array(_)     => Unexpected::Seq,
+        Value::Object(_)    => Unexpected::Map,
+    }
+}
+
+/// Blame `node` for `err`, unless a child already claimed it.
+fn at_node<V>(node: &Spanned<V>, mut err: Error) -> Error {
+    if err.range().is_none() { err.set_range(node.range()); }
+    err
+}
+
+
+
+struct SeqAccess<'de> {
+    node:   Node<'de>,
+    iter:   std::iter::Enumerate<std::slice::Iter<'de, spanned::Value>>,
+}
+
+impl<'de> SeqAccess<'de> {
+    fn is_skipped(&self, value: &spanned::Value) -> bool { self.node.is_skipped(value) }
+    fn remaining(&self) -> usize { self.iter.clone().filter(|(_, value)| !self.is_skipped(value)).count() }
+}
+
+impl<'de> de::SeqAccess<'de> for &mut SeqAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        while let Some((index, value)) = self.iter.next() {
+            if self.is_skipped(value) { continue }
+            return seed.deserialize(self.node.child(value)).map(Some).map_err(|err| err.at_index(index));
+        }
+        Ok(None)
+    }
+
+    fn size_hint(&self) -> Option<usize> { Some(self.remaining()) }
+}
+
+
+
+struct MapAccess<'de> {
+    node:   Node<'de>,
+    iter:   <&'de Map<spanned::String, spanned::Value> as IntoIterator>::IntoIter,
+    /// Missing fields to fill with [Placeholder]s, after the entries of `iter`
+    added:  std::slice::Iter<'de, &'static str>,
+    value:  Option<(&'de str, Entry<'de>)>,
+}
+
+enum Entry<'de> {
+    Parsed(Node<'de>),
+    Added(Placeholder),
+}
+
+impl<'de> MapAccess<'de> {
+    fn is_skipped(&self, key: &spanned::String) -> bool { self.node.is_skipped(key) }
+    fn remaining(&self) -> usize { self.iter.clone().filter(|(key, _)| !self.is_skipped(key)).count() }
+}
+
+impl<'de> de::MapAccess<'de> for &mut MapAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        while let Some((key, value)) = self.iter.next() {
+            if self.is_skipped(key) { continue }
+            self.value = Some((key.as_str(), Entry::Parsed(self.node.child(value))));
+            return seed.deserialize(MapKey(key)).map(Some);
+        }
+        match self.added.next() {
+            Some(field) => {
+                self.value = Some((field, Entry::Added(Placeholder::new(self.node.value.range()))));
+                seed.deserialize(BorrowedStrDeserializer::new(field)).map(Some)
+            },
+            None        => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let (key, value) = self.value.take().ok_or_else(|| <Error as de::Error>::custom("value is missing"))?;
+        match value {
+            Entry::Parsed(node)         => seed.deserialize(node),
+            Entry::Added(placeholder)   => seed.deserialize(placeholder),
+        }.map_err(|err| err.at_key(key))
+    }
+
+    fn size_hint(&self) -> Option<usize> { Some(self.remaining() + self.added.len()) }
+}
+
+
+
+macro_rules! deserialize_parsed_key {
+    ($( $method:ident => $visit:ident, )*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+            let key = self.0.get_ref().as_str();
+            key.parse().map_err(|_| de::Error::invalid_type(Unexpected::Str(key), &visitor)).and_then(|v| visitor.$visit(v)).map_err(|err| at_node(self.0, err))
+        }
+    )*};
+}
+
+/// Object keys are always strings in JSON, but may be deserialized as numbers or bools (e.g. `HashMap<u32, _>`.)
+struct MapKey<'de>(&'de spanned::String);
+
+impl<'de> de::Deserializer<'de> for MapKey<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_borrowed_str(self.0.get_ref()).map_err(|err| at_node(self.0, err))
+    }
+
+    deserialize_parsed_key! {
+        deserialize_bool    => visit_bool,
+        deserialize_i8      => visit_i8,
+        deserialize_i16     => visit_i16,
+        deserialize_i32     => visit_i32,
+        deserialize_i64     => visit_i64,
+        deserialize_i128    => visit_i128,
+        deserialize_u8      => visit_u8,
+        deserialize_u16     => visit_u16,
+        deserialize_u32     => visit_u32,
+        deserialize_u64     => visit_u64,
+        deserialize_u128    => visit_u128,
+        deserialize_f32     => visit_f32,
+        deserialize_f64     => visit_f64,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> { visitor.visit_some(self) }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V) -> Result<V::Value> {
+        if name == spanned::NAME {
+            visitor.visit_map(SpannedAccess::new(self.0.range(), self))
+        } else {
+            visitor.visit_newtype_struct(self)
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        visitor.visit_enum(BorrowedStrDeserializer::new(self.0.get_ref())).map_err(|err| at_node(self.0, err))
+    }
+
+    serde::forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+
+
+/// `{"Variant": ...}`
+struct VariantAccess<'de> {
+    key:    &'de spanned::String,
+    value:  Node<'de>,
+}
+
+impl<'de> de::EnumAccess<'de> for VariantAccess<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let variant = seed.deserialize(MapKey(self.key))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantAccess<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        de::Deserialize::deserialize(self.value).map_err(|err| err.at_key(self.key))
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self.value).map_err(|err| err.at_key(self.key))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(self.value, visitor).map_err(|err| err.at_key(self.key))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_map(self.value, visitor).map_err(|err| err.at_key(self.key))
+    }
+}
+
+
+
+/// Feeds a [Spanned] its original start, value, and end as if they were fields of a map.
+pub(crate) struct SpannedAccess<D> {
+    start:  usize,
+    end:    usize,
+    value:  Option<D>,
+    field:  usize,
+}
+
+impl<D> SpannedAccess<D> {
+    pub(crate) fn new(range: std::ops::Range<usize>, value: D) -> Self { Self { start: range.start, end: range.end, value: Some(value), field: 0 } }
+}
+
+impl<'de, D: de::Deserializer<'de, Error = Error>> de::MapAccess<'de> for SpannedAccess<D> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match spanned::FIELDS.get(self.field) {
+            Some(field) => seed.deserialize(BorrowedStrDeserializer::new(field)).map(Some),
+            None        => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        self.field += 1;
+        match self.field {
+            1       => seed.deserialize((self.start as u64).into_deserializer()),
+            2       => seed.deserialize(self.value.take().ok_or_else(|| <Error as de::Error>::custom("value is missing"))?),
+            _end    => seed.deserialize((self.end as u64).into_deserializer()),
+        }
+    }
+}